@@ -1,15 +1,33 @@
 use crate::{
+    options::RenderOptions,
     puppet,
-    tracker::{spawn_tracker, TrackerOptions},
+    puppet::offline::{poses, render_frame, write_png},
+    tracker::{backend_from_options, read_recording, Backoff, Tracker, TrackerState},
     Options,
 };
-use std::sync::mpsc::channel;
-use three_d::{Window, WindowSettings};
+use log::{error, info};
+use std::env;
+use std::fs;
+use std::process::exit;
+use three_d::{HeadlessContext, Window, WindowSettings};
 
 pub fn run_cli() {
+    let mut args: Vec<String> = env::args().collect();
+
+    if args.get(1).map(String::as_str) == Some("render") {
+        args.remove(1);
+        return run_offline_render(RenderOptions::from_arguments(args));
+    }
+
     let options = Options::from_arguments();
 
-    let (control_tx, control_rx) = channel();
+    let rig = match puppet::Rig::open(options.path.as_path()) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("could not load rig: {}", e);
+            exit(1);
+        }
+    };
 
     let window = Window::new(WindowSettings {
         title: "layertuber".to_string(),
@@ -17,11 +35,51 @@ pub fn run_cli() {
     })
     .unwrap();
 
-    let (report_rx, _tracker_thread) = spawn_tracker(TrackerOptions::from(&options), control_rx);
+    // the tracker lives in the render closure, which is dropped (stopping the tracker) when the
+    // window is closed
+    let tracker = Tracker::spawn(backend_from_options(&options), Backoff::default());
 
     let context = window.gl();
 
-    let rig = puppet::Rig::open(options.path.as_path()).unwrap();
+    window.render_loop(puppet::render(context, tracker, rig))
+}
+
+fn run_offline_render(options: RenderOptions) {
+    let fail = |message: String| -> ! {
+        error!("{}", message);
+        exit(1)
+    };
+
+    let reports = read_recording(&options.reports)
+        .unwrap_or_else(|e| fail(format!("could not read tracking reports: {}", e)));
+
+    let rig = puppet::Rig::open(options.path.as_path())
+        .unwrap_or_else(|e| fail(format!("could not load rig: {}", e)));
+
+    fs::create_dir_all(&options.output)
+        .unwrap_or_else(|e| fail(format!("could not create output directory: {}", e)));
+
+    let context = HeadlessContext::new()
+        .unwrap_or_else(|e| fail(format!("could not create graphics context: {}", e)));
+
+    let mut puppet = puppet::Puppet::new(&context, &rig);
+    let mut frames = 0;
+
+    for (index, report) in poses(&rig, &reports, options.fps).enumerate() {
+        let pixels = render_frame(
+            &context,
+            &mut puppet,
+            options.width,
+            options.height,
+            &report,
+            TrackerState::Running,
+        );
+
+        let path = options.output.join(format!("{:06}.png", index));
+        write_png(&path, options.width, options.height, &pixels)
+            .unwrap_or_else(|e| fail(format!("could not write {}: {}", path.display(), e)));
+        frames += 1;
+    }
 
-    window.render_loop(puppet::render(context, report_rx, control_tx, rig))
+    info!("rendered {} frames to {}", frames, options.output.display());
 }
@@ -1,29 +1,19 @@
 use crate::{
     options::Options,
-    puppet::{render, Rig},
-    tracker::{spawn_tracker, TrackerOptions},
+    puppet::{render, Rig, RigError},
+    tracker::{backend_from_options, Backoff, Tracker, TrackerControl},
 };
-use log::error;
-use std::sync::mpsc::channel;
 use three_d::{Context, FrameInput, FrameOutput};
 
+/// the renderer, and a way to talk to the tracker it's using
 pub fn create_renderer(
     context: Context,
     options: Options,
-) -> Box<dyn FnMut(FrameInput) -> FrameOutput> {
-    let (control_tx, control_rx) = channel();
+) -> Result<(Box<dyn FnMut(FrameInput) -> FrameOutput>, TrackerControl), RigError> {
+    let rig = Rig::open(options.path.as_path())?;
 
-    let rig_path = options.path.as_path();
+    let tracker = Tracker::spawn(backend_from_options(&options), Backoff::default());
+    let control = tracker.control();
 
-    let (report_rx, _thread) = spawn_tracker(TrackerOptions::from(&options), control_rx);
-
-    let rig = match Rig::open(rig_path) {
-        Ok(r) => r,
-        Err(e) => {
-            error!("error loading rig: {}", e);
-            panic!("this should show an error message in OBS somehow");
-        }
-    };
-
-    render(context, report_rx, control_tx, rig)
+    Ok((render(context, tracker, rig), control))
 }
@@ -1,6 +1,7 @@
 use super::render::create_renderer;
 use crate::options::Options;
-use log::info;
+use crate::tracker::{ControlMessage, TrackerControl, TrackerOptions, MODELS};
+use log::{error, info};
 use obs_wrapper::{
     data::DataObj,
     graphics::{GraphicsColorFormat, GraphicsTexture},
@@ -18,38 +19,69 @@ const SETTING_WIDTH: ObsString = obs_string!("width");
 const SETTING_HEIGHT: ObsString = obs_string!("height");
 const SETTING_CAMERA_INDEX: ObsString = obs_string!("camera_index");
 const SETTING_SHOW_FEATURES: ObsString = obs_string!("show_features");
+const SETTING_CAPTURE_WIDTH: ObsString = obs_string!("capture_width");
+const SETTING_CAPTURE_HEIGHT: ObsString = obs_string!("capture_height");
+const SETTING_FPS: ObsString = obs_string!("fps");
+const SETTING_MODEL: ObsString = obs_string!("model");
+const SETTING_MAX_FACES: ObsString = obs_string!("max_faces");
 
 pub struct PuppetSource {
     tex: GraphicsTexture,
     path: Option<String>,
-    camera_index: u8,
-    show_features: bool,
+    tracker_options: TrackerOptions,
     context: Context,
     render: Option<Box<dyn FnMut(FrameInput) -> FrameOutput>>,
+    tracker: Option<TrackerControl>,
 }
 
 impl PuppetSource {
     fn end_rendering(&mut self) {
         self.render = None;
+        self.tracker = None;
     }
 
     fn start_rendering(&mut self) {
-        self.render = match &self.path {
-            Some(p) => Some(create_renderer(
+        (self.render, self.tracker) = match &self.path {
+            Some(p) => match create_renderer(
                 self.context.clone(),
                 Options {
                     path: Path::new(p).to_path_buf(),
-                    camera_index: self.camera_index,
-                    show_features: self.show_features,
+                    camera_index: self.tracker_options.camera_index,
+                    show_features: self.tracker_options.show_features,
+                    capture_width: self.tracker_options.capture_width,
+                    capture_height: self.tracker_options.capture_height,
+                    fps: self.tracker_options.fps,
+                    model: self.tracker_options.model,
+                    max_faces: self.tracker_options.max_faces,
+                    record: None,
+                    replay: None,
+                    replay_speed: 1.0,
+                    openseeface: None,
+                    vmc: None,
+                    ifacialmocap: None,
                 },
-            )),
+            ) {
+                Ok((render, tracker)) => (Some(render), Some(tracker)),
+                Err(e) => {
+                    error!("could not load puppet {}: {}", p, e);
+                    (None, None)
+                }
+            },
             None => {
                 info!("path not set");
-                None
+                (None, None)
             }
         }
     }
 
+    /// make the current pose the neutral one
+    fn calibrate(&self) {
+        match &self.tracker {
+            Some(tracker) => tracker.send(ControlMessage::Calibrate),
+            None => info!("not tracking, so there's nothing to calibrate"),
+        }
+    }
+
     fn update_settings(&mut self, settings: &DataObj) {
         let path: Option<Cow<'_, str>> = settings.get(SETTING_PATH);
         self.path = path.map(|p| p.into_owned());
@@ -60,12 +92,34 @@ impl PuppetSource {
             }
         }
 
+        let options = &mut self.tracker_options;
+
         if let Some(camera_index) = settings.get(SETTING_CAMERA_INDEX) {
-            self.camera_index = camera_index
+            options.camera_index = camera_index
         }
 
         if let Some(show_features) = settings.get(SETTING_SHOW_FEATURES) {
-            self.show_features = show_features
+            options.show_features = show_features
+        }
+
+        if let Some(capture_width) = settings.get(SETTING_CAPTURE_WIDTH) {
+            options.capture_width = capture_width
+        }
+
+        if let Some(capture_height) = settings.get(SETTING_CAPTURE_HEIGHT) {
+            options.capture_height = capture_height
+        }
+
+        if let Some(fps) = settings.get(SETTING_FPS) {
+            options.fps = fps
+        }
+
+        if let Some(model) = settings.get(SETTING_MODEL) {
+            options.model = model
+        }
+
+        if let Some(max_faces) = settings.get(SETTING_MAX_FACES) {
+            options.max_faces = max_faces
         }
 
         self.render_one_frame();
@@ -137,9 +191,9 @@ impl Sourceable for PuppetSource {
         let mut source = PuppetSource {
             tex: GraphicsTexture::new(100, 100, GraphicsColorFormat::RGBA),
             path: None,
-            camera_index: 0,
-            show_features: false,
+            tracker_options: TrackerOptions::default(),
             render: None,
+            tracker: None,
             context: (*context).clone(),
         };
         source.update_settings(&create.settings);
@@ -147,10 +201,9 @@ impl Sourceable for PuppetSource {
         create.register_hotkey(
             obs_string!("calibrate"),
             obs_string!("Reset puppet position"),
-            |key, _data| {
+            |key, source| {
                 if key.pressed {
-                    // XXX implement this
-                    info!("calibration requested")
+                    source.calibrate()
                 }
             },
         );
@@ -193,6 +246,36 @@ impl GetPropertiesSource for PuppetSource {
             BoolProp,
         );
 
+        properties.add(
+            SETTING_CAPTURE_WIDTH,
+            obs_string!("Camera width (in pixels)"),
+            NumberProp::new_int().with_range(1..(2_u32).pow(16)),
+        );
+
+        properties.add(
+            SETTING_CAPTURE_HEIGHT,
+            obs_string!("Camera height (in pixels)"),
+            NumberProp::new_int().with_range(1..(2_u32).pow(16)),
+        );
+
+        properties.add(
+            SETTING_FPS,
+            obs_string!("Camera frames per second"),
+            NumberProp::new_int().with_range(1..241_u32),
+        );
+
+        properties.add(
+            SETTING_MODEL,
+            obs_string!("Tracking model (-3 is fastest, 3 most accurate, 4 best at winks)"),
+            NumberProp::new_int().with_range(MODELS),
+        );
+
+        properties.add(
+            SETTING_MAX_FACES,
+            obs_string!("Faces to look for"),
+            NumberProp::new_int().with_range(1..=16_u8),
+        );
+
         properties
     }
 }
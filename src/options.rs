@@ -1,18 +1,45 @@
-use argparse::{ArgumentParser, Store, StoreTrue};
+use crate::tracker::{TrackerOptions, MODELS};
+use argparse::{ArgumentParser, ParseOption, Store, StoreTrue};
+use std::io::{stderr, stdout};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::process::exit;
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub path: PathBuf,
     pub camera_index: u8,
     pub show_features: bool,
+    pub capture_width: u32,
+    pub capture_height: u32,
+    pub fps: u32,
+    pub model: i8,
+    pub max_faces: u8,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub replay_speed: f32,
+    pub openseeface: Option<u16>,
+    pub vmc: Option<u16>,
+    pub ifacialmocap: Option<IpAddr>,
 }
 
 impl Options {
     pub fn from_arguments() -> Self {
+        let defaults = TrackerOptions::default();
         let mut path_str = String::new();
-        let mut camera_index: u8 = 0;
-        let mut show_features = false;
+        let mut camera_index: u8 = defaults.camera_index;
+        let mut show_features = defaults.show_features;
+        let mut capture_width: u32 = defaults.capture_width;
+        let mut capture_height: u32 = defaults.capture_height;
+        let mut fps: u32 = defaults.fps;
+        let mut model: i8 = defaults.model;
+        let mut max_faces: u8 = defaults.max_faces;
+        let mut record: Option<PathBuf> = None;
+        let mut replay: Option<PathBuf> = None;
+        let mut replay_speed: f32 = 1.0;
+        let mut openseeface: Option<u16> = None;
+        let mut vmc: Option<u16> = None;
+        let mut ifacialmocap: Option<String> = None;
 
         {
             let mut parser: ArgumentParser = ArgumentParser::new();
@@ -40,13 +67,210 @@ impl Options {
                 "Show an additional window with your webcam feed and facial feature detection spots overlaid on it."
             );
 
+            parser.refer(&mut capture_width).add_option(
+                &["--capture-width"],
+                Store,
+                "The width, in pixels, to ask the camera for. It may pick the nearest it can do instead.",
+            );
+
+            parser.refer(&mut capture_height).add_option(
+                &["--capture-height"],
+                Store,
+                "The height, in pixels, to ask the camera for.",
+            );
+
+            parser.refer(&mut fps).add_option(
+                &["--fps"],
+                Store,
+                "How many frames per second to ask the camera for.",
+            );
+
+            parser.refer(&mut model).add_option(
+                &["--model"],
+                Store,
+                concat!(
+                    "Which OpenSeeFace model to track with, from -3 (fastest, but least accurate) to 4. ",
+                    "3 is the most accurate, and 4 is a little better at winks."
+                ),
+            );
+
+            parser.refer(&mut max_faces).add_option(
+                &["--max-faces"],
+                Store,
+                "How many faces to look for. Only the first one found moves the puppet.",
+            );
+
+            parser.refer(&mut record).add_option(
+                &["--record"],
+                ParseOption,
+                "Write every tracking report to this file, so that the session can be replayed later.",
+            );
+
+            parser.refer(&mut replay).add_option(
+                &["--replay"],
+                ParseOption,
+                concat!(
+                    "Instead of tracking your face, replay tracking reports from a file written with --record. ",
+                    "No webcam is needed."
+                ),
+            );
+
+            parser.refer(&mut replay_speed).add_option(
+                &["--replay-speed"],
+                Store,
+                concat!(
+                    "How fast to replay a recording, relative to how it was recorded. ",
+                    "0 replays without waiting between reports."
+                ),
+            );
+
+            parser.refer(&mut openseeface).add_option(
+                &["--openseeface"],
+                ParseOption,
+                concat!(
+                    "Instead of running the bundled tracker, listen on this UDP port for packets from an ",
+                    "OpenSeeFace you're running yourself. OpenSeeFace sends to port 11573 by default."
+                ),
+            );
+
+            parser.refer(&mut vmc).add_option(
+                &["--vmc"],
+                ParseOption,
+                concat!(
+                    "Instead of running the bundled tracker, listen on this UDP port for the Virtual Motion ",
+                    "Capture protocol. Blendshapes become channels with the same names. ",
+                    "VMC usually uses port 39539."
+                ),
+            );
+
+            parser.refer(&mut ifacialmocap).add_option(
+                &["--ifacialmocap"],
+                ParseOption,
+                concat!(
+                    "Instead of running the bundled tracker, ask iFacialMocap running on the phone at this IP ",
+                    "address to send us its tracking. ARKit's blendshapes become channels with their usual names."
+                ),
+            );
+
             parser.parse_args_or_exit();
         }
 
-        return Options {
+        if !MODELS.contains(&model) {
+            eprintln!(
+                "there is no model {}; pick one from {} to {}",
+                model,
+                MODELS.start(),
+                MODELS.end()
+            );
+            exit(2);
+        }
+
+        if max_faces == 0 {
+            eprintln!("--max-faces must be at least 1");
+            exit(2);
+        }
+
+        Options {
             path: Path::new(&path_str).into(),
             camera_index,
             show_features,
-        };
+            capture_width,
+            capture_height,
+            fps,
+            model,
+            max_faces,
+            record,
+            replay,
+            replay_speed,
+            openseeface,
+            vmc,
+            ifacialmocap: ifacialmocap.map(|address| {
+                address.parse().unwrap_or_else(|e| {
+                    eprintln!("{} is not an IP address: {}", address, e);
+                    exit(2)
+                })
+            }),
+        }
+    }
+}
+
+/// Options for `layertuber render`, which renders a recording to images instead of opening a window
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub path: PathBuf,
+    pub reports: PathBuf,
+    pub output: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+impl RenderOptions {
+    /// parse `args`, which should not include the `render` subcommand itself
+    pub fn from_arguments(args: Vec<String>) -> Self {
+        let mut path_str = String::new();
+        let mut reports_str = String::new();
+        let mut output_str = String::new();
+        let mut width: u32 = 1280;
+        let mut height: u32 = 720;
+        let mut fps: u32 = 30;
+
+        {
+            let mut parser: ArgumentParser = ArgumentParser::new();
+            parser.set_description(
+                "Render a puppet to a PNG per frame of tracking reports, without a window.",
+            );
+
+            parser.refer(&mut path_str).required().add_argument(
+                "puppet",
+                Store,
+                "The path of the OpenRaster file you want to use as a puppet.",
+            );
+
+            parser.refer(&mut reports_str).required().add_argument(
+                "reports",
+                Store,
+                "A file of tracking reports, one JSON object per line, as written by --record.",
+            );
+
+            parser.refer(&mut output_str).required().add_argument(
+                "output",
+                Store,
+                "The directory to write frames to. It will be created if it does not exist.",
+            );
+
+            parser
+                .refer(&mut width)
+                .add_option(&["--width"], Store, "Frame width, in pixels.");
+
+            parser
+                .refer(&mut height)
+                .add_option(&["--height"], Store, "Frame height, in pixels.");
+
+            parser.refer(&mut fps).add_option(
+                &["--fps"],
+                Store,
+                "Frames per second of tracking reports to render. Reports without times are \
+                 taken to be one frame apart.",
+            );
+
+            if let Err(code) = parser.parse(args, &mut stdout(), &mut stderr()) {
+                exit(code);
+            }
+        }
+
+        if fps == 0 {
+            eprintln!("--fps must be at least 1");
+            exit(2);
+        }
+
+        RenderOptions {
+            path: Path::new(&path_str).into(),
+            reports: Path::new(&reports_str).into(),
+            output: Path::new(&output_str).into(),
+            width,
+            height,
+            fps,
+        }
     }
 }
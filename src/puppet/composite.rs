@@ -0,0 +1,159 @@
+//! OpenRaster composite ops, and how to get OpenGL blending to do them
+
+use log::warn;
+use three_d::{Blend, BlendEquationType, BlendMultiplierType};
+
+/// The composite ops we can draw. Everything except `SrcOver` is drawn from a premultiplied
+/// texture, since that's what lets these be expressed as plain blending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositeOp {
+    SrcOver,
+    Multiply,
+    Screen,
+    Plus,
+    Lighten,
+    DstIn,
+    DstOut,
+}
+
+impl CompositeOp {
+    /// the op for an OpenRaster `composite-op` attribute, if it's one we can draw
+    pub fn from_ora(name: &str) -> Option<CompositeOp> {
+        match name {
+            "svg:src-over" => Some(CompositeOp::SrcOver),
+            "svg:multiply" => Some(CompositeOp::Multiply),
+            "svg:screen" => Some(CompositeOp::Screen),
+            "svg:plus" | "svg:add" => Some(CompositeOp::Plus),
+            "svg:lighten" => Some(CompositeOp::Lighten),
+            "svg:dst-in" => Some(CompositeOp::DstIn),
+            "svg:dst-out" => Some(CompositeOp::DstOut),
+            _ => None,
+        }
+    }
+
+    /// the op to draw the layer called `layer` with, given its `composite-op` attribute. layers
+    /// using ops we can't draw are drawn normally instead
+    pub fn for_layer(layer: &str, name: &str) -> CompositeOp {
+        CompositeOp::from_ora(name).unwrap_or_else(|| {
+            warn!(
+                "layer {} uses {}, which we can't draw; drawing it normally",
+                layer, name
+            );
+            CompositeOp::SrcOver
+        })
+    }
+
+    pub fn premultiplied(&self) -> bool {
+        *self != CompositeOp::SrcOver
+    }
+
+    pub fn blend(&self) -> Blend {
+        use BlendMultiplierType::*;
+
+        let (source_rgb_multiplier, destination_rgb_multiplier, rgb_equation) = match self {
+            CompositeOp::SrcOver => return Blend::TRANSPARENCY,
+            // dst × src + dst × (1 - src alpha)
+            CompositeOp::Multiply => (DstColor, OneMinusSrcAlpha, BlendEquationType::Add),
+            // src + dst × (1 - src)
+            CompositeOp::Screen => (One, OneMinusSrcColor, BlendEquationType::Add),
+            CompositeOp::Plus => (One, One, BlendEquationType::Add),
+            // multipliers are ignored for max
+            CompositeOp::Lighten => (One, One, BlendEquationType::Max),
+            CompositeOp::DstIn => (Zero, SrcAlpha, BlendEquationType::Add),
+            CompositeOp::DstOut => (Zero, OneMinusSrcAlpha, BlendEquationType::Add),
+        };
+
+        Blend::Enabled {
+            source_rgb_multiplier,
+            destination_rgb_multiplier,
+            rgb_equation,
+            // leave the target's alpha alone, like Blend::TRANSPARENCY does
+            source_alpha_multiplier: Zero,
+            destination_alpha_multiplier: One,
+            alpha_equation: BlendEquationType::Add,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    /// keeps every warning, so we can check we were told about the ops we can't draw
+    struct Warnings(Mutex<Vec<String>>);
+
+    impl Log for Warnings {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Warn
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static WARNINGS: Warnings = Warnings(Mutex::new(Vec::new()));
+
+    #[test]
+    fn ora_names_are_parsed() {
+        let ops = [
+            ("svg:src-over", CompositeOp::SrcOver),
+            ("svg:multiply", CompositeOp::Multiply),
+            ("svg:screen", CompositeOp::Screen),
+            ("svg:plus", CompositeOp::Plus),
+            ("svg:add", CompositeOp::Plus),
+            ("svg:lighten", CompositeOp::Lighten),
+            ("svg:dst-in", CompositeOp::DstIn),
+            ("svg:dst-out", CompositeOp::DstOut),
+        ];
+
+        for (name, op) in ops {
+            assert_eq!(CompositeOp::from_ora(name), Some(op), "{}", name);
+        }
+
+        assert_eq!(CompositeOp::from_ora("svg:overlay"), None);
+        assert_eq!(CompositeOp::from_ora("multiply"), None);
+        assert_eq!(CompositeOp::from_ora(""), None);
+    }
+
+    #[test]
+    fn ops_we_cant_draw_are_drawn_normally_with_a_warning() {
+        // other tests may have set a logger already, in which case there's nothing to check
+        let logging = log::set_logger(&WARNINGS).is_ok();
+        log::set_max_level(log::LevelFilter::Warn);
+
+        assert_eq!(
+            CompositeOp::for_layer("shading", "svg:overlay"),
+            CompositeOp::SrcOver
+        );
+        assert_eq!(
+            CompositeOp::for_layer("highlights", "svg:screen"),
+            CompositeOp::Screen
+        );
+
+        if logging {
+            let warnings = WARNINGS.0.lock().unwrap();
+            assert!(
+                warnings.iter().any(|w| w
+                    == "layer shading uses svg:overlay, which we can't draw; drawing it normally"),
+                "{:?}",
+                warnings
+            );
+            assert!(!warnings.iter().any(|w| w.contains("highlights")));
+        }
+    }
+
+    #[test]
+    fn only_src_over_is_straight_alpha() {
+        assert!(!CompositeOp::SrcOver.premultiplied());
+        assert_eq!(CompositeOp::SrcOver.blend(), Blend::TRANSPARENCY);
+        assert!(CompositeOp::Multiply.premultiplied());
+        assert!(CompositeOp::Screen.premultiplied());
+    }
+}
@@ -0,0 +1,194 @@
+//! turning config problems into messages that point at the offending part of the yaml
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// a single problem with a config file, with a position if we know it
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// 1-based line and column
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// everything wrong with a config file
+#[derive(Debug)]
+pub struct Diagnostics {
+    pub path: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "problems in {}:", self.path.display())?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<serde_yaml::Error> for Diagnostic {
+    fn from(err: serde_yaml::Error) -> Diagnostic {
+        let mut message = err.to_string();
+
+        // the position is part of serde_yaml's message, but we report it separately
+        if let Some(index) = message.rfind(" at line ") {
+            message.truncate(index);
+        }
+
+        if let Some(suggestion) = suggestion_for(&message) {
+            message = format!("{} (did you mean `{}`?)", message, suggestion);
+        }
+
+        Diagnostic {
+            position: err.location().map(|l| (l.line(), l.column())),
+            message,
+        }
+    }
+}
+
+/// serde reports unknown fields and variants as "unknown field `x`, expected one of `a`, `b`";
+/// pick out the expected name that's closest to what was written, if any is close enough to
+/// plausibly be a typo
+fn suggestion_for(message: &str) -> Option<&str> {
+    let rest = message
+        .split_once("unknown field `")
+        .or_else(|| message.split_once("unknown variant `"))?
+        .1;
+    let (unknown, expected) = rest.split_once('`')?;
+
+    closest(unknown, expected.split('`').skip(1).step_by(2))
+}
+
+/// the candidate closest to `unknown`, if any is close enough to plausibly be a typo
+pub fn closest<'a>(
+    unknown: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(unknown, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (unknown.len().max(candidate.len()) / 2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// find where `key` is defined as a layer in config yaml, so that we can point at it
+pub fn find_layer_key(source: &str, key: &str) -> Option<(usize, usize)> {
+    find_key(source, "layers", key)
+}
+
+/// find where `key` is defined in the top-level `section` mapping of config yaml, whether that's
+/// written as a block or as `section: {key: value}`
+pub fn find_key(source: &str, section: &str, key: &str) -> Option<(usize, usize)> {
+    let candidates = [
+        format!("{}:", key),
+        format!("\"{}\":", key),
+        format!("'{}':", key),
+    ];
+    let is_key = |s: &str| candidates.iter().any(|k| s.starts_with(k.as_str()));
+    let section = format!("{}:", section);
+    let mut in_section = false;
+    let mut key_indent = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if indent == 0 {
+            in_section = trimmed.starts_with(&section);
+            if in_section {
+                let flow = &trimmed[section.len()..];
+                if let Some(column) = find_flow_key(flow, is_key) {
+                    return Some((index + 1, section.len() + column + 1));
+                }
+            }
+            continue;
+        }
+
+        if !in_section || *key_indent.get_or_insert(indent) != indent {
+            continue;
+        }
+
+        if is_key(trimmed) {
+            return Some((index + 1, indent + 1));
+        }
+    }
+
+    None
+}
+
+/// where a key that `is_key` likes starts in `flow`, a yaml flow mapping like `{a: 1, b: 2}`,
+/// looking only at the mapping's own keys and not those of anything inside it
+fn find_flow_key(flow: &str, is_key: impl Fn(&str) -> bool) -> Option<usize> {
+    let mut depth = 0;
+
+    for (column, c) in flow.char_indices() {
+        match c {
+            '#' if depth == 0 => return None,
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => (),
+        }
+
+        if depth == 1 && (c == '{' || c == ',') {
+            let rest = &flow[column + 1..];
+            let start = rest.len() - rest.trim_start().len();
+            if is_key(rest.trim_start()) {
+                return Some(column + 1 + start);
+            }
+        }
+    }
+
+    None
+}
+
+/// find the first place `name` is written as a whole word in config yaml, outside of comments
+pub fn find_channel(source: &str, name: &str) -> Option<(usize, usize)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(code, _)| code);
+
+        for (column, _) in line.match_indices(name) {
+            let before = line[..column].chars().next_back();
+            let after = line[column + name.len()..].chars().next();
+
+            if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                return Some((index + 1, column + 1));
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,200 @@
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+/// a way of smoothing out a jittery source, written like `{type: ema, alpha: 0.5}`
+#[derive(Debug, Deserialize, Copy, Clone)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FilterConfig {
+    /// the One Euro filter: smooths heavily when a source is still, and lightly when it moves
+    /// quickly, so it cuts jitter without adding much lag
+    OneEuro {
+        /// the cutoff frequency, in Hz, when the source is still. lower is smoother
+        #[serde(default = "default_min_cutoff")]
+        min_cutoff: f32,
+
+        /// how much the cutoff rises with speed. higher is less laggy
+        #[serde(default)]
+        beta: f32,
+
+        /// the cutoff frequency, in Hz, for the estimate of speed
+        #[serde(default = "default_derivative_cutoff")]
+        derivative_cutoff: f32,
+    },
+
+    /// an exponential moving average. `alpha` is how much of each new value to take, from 0 (never
+    /// move) to 1 (no smoothing)
+    Ema { alpha: f32 },
+}
+
+fn default_min_cutoff() -> f32 {
+    1.0
+}
+
+fn default_derivative_cutoff() -> f32 {
+    1.0
+}
+
+/// how much of a new value to take when low-pass filtering at `cutoff` Hz, `dt` seconds after the
+/// last value
+fn smoothing_factor(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+/// What a filter needs to remember between values
+#[derive(Debug, Clone)]
+pub struct FilterState {
+    value: Vec<f32>,
+    derivative: Vec<f32>,
+}
+
+impl FilterState {
+    /// the most recent filtered value
+    pub fn value(&self) -> &[f32] {
+        &self.value
+    }
+}
+
+impl FilterConfig {
+    /// filter `values` in place, `dt` seconds after `state` was last updated
+    pub fn apply(&self, state: &mut Option<FilterState>, dt: f32, values: &mut [f32]) {
+        let state = state.get_or_insert_with(|| FilterState {
+            value: values.to_vec(),
+            derivative: vec![0.0; values.len()],
+        });
+
+        if dt <= 0.0 {
+            values.copy_from_slice(&state.value);
+            return;
+        }
+
+        for (i, value) in values.iter_mut().enumerate() {
+            let previous = state.value[i];
+
+            let alpha = match *self {
+                FilterConfig::Ema { alpha } => alpha.clamp(0.0, 1.0),
+                FilterConfig::OneEuro {
+                    min_cutoff,
+                    beta,
+                    derivative_cutoff,
+                } => {
+                    let derivative = (*value - previous) / dt;
+                    state.derivative[i] += (derivative - state.derivative[i])
+                        * smoothing_factor(derivative_cutoff, dt);
+                    smoothing_factor(min_cutoff + beta * state.derivative[i].abs(), dt)
+                }
+            };
+
+            *value = previous + (*value - previous) * alpha;
+            state.value[i] = *value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_EURO: FilterConfig = FilterConfig::OneEuro {
+        min_cutoff: 1.0,
+        beta: 0.0,
+        derivative_cutoff: 1.0,
+    };
+
+    const EMA: FilterConfig = FilterConfig::Ema { alpha: 0.5 };
+
+    /// filter a step from 0 to 1 (and from 0 to -2 alongside it), `dt` seconds apart, returning
+    /// what came out each time
+    fn step(filter: FilterConfig, dt: f32, steps: usize) -> Vec<[f32; 2]> {
+        let mut state = None;
+        let mut values = [0.0, 0.0];
+        filter.apply(&mut state, dt, &mut values);
+
+        (0..steps)
+            .map(|_| {
+                let mut values = [1.0, -2.0];
+                filter.apply(&mut state, dt, &mut values);
+                values
+            })
+            .collect()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn the_first_value_passes_through() {
+        for filter in [ONE_EURO, EMA] {
+            let mut state = None;
+            let mut values = [0.25, 0.5];
+            filter.apply(&mut state, 1.0 / 30.0, &mut values);
+            assert_eq!(values, [0.25, 0.5]);
+            assert_eq!(state.unwrap().value(), [0.25, 0.5]);
+        }
+    }
+
+    #[test]
+    fn ema_takes_alpha_of_each_new_value() {
+        let steps = step(EMA, 1.0 / 30.0, 20);
+        assert!(close(steps[0][0], 0.5), "{:?}", steps);
+        assert!(close(steps[1][0], 0.75), "{:?}", steps);
+        assert!(close(steps[2][0], 0.875), "{:?}", steps);
+        assert!(close(steps[2][1], -1.75), "{:?}", steps);
+        assert!(close(steps[19][0], 1.0), "{:?}", steps);
+
+        // however long it's been
+        assert_eq!(step(EMA, 1.0, 3), steps[..3]);
+    }
+
+    #[test]
+    fn one_euro_converges() {
+        let steps = step(ONE_EURO, 1.0 / 30.0, 90);
+
+        // smoothly, never overshooting
+        assert!(steps[0][0] > 0.0 && steps[0][0] < 0.5, "{:?}", steps[0]);
+        assert!(steps
+            .windows(2)
+            .all(|w| w[0][0] <= w[1][0] && w[1][0] <= 1.0));
+        assert!(close(steps[89][0], 1.0), "{:?}", steps[89]);
+        assert!(close(steps[89][1], -2.0), "{:?}", steps[89]);
+    }
+
+    #[test]
+    fn one_euro_moves_further_the_longer_it_has_been() {
+        let quick = step(ONE_EURO, 1.0 / 60.0, 1)[0][0];
+        let slow = step(ONE_EURO, 1.0 / 10.0, 1)[0][0];
+        assert!(quick < slow, "{} {}", quick, slow);
+
+        // and keeps up with fast movement when beta says to
+        let eager = FilterConfig::OneEuro {
+            min_cutoff: 1.0,
+            beta: 1.0,
+            derivative_cutoff: 1.0,
+        };
+        let eager = step(eager, 1.0 / 60.0, 1)[0][0];
+        assert!(quick < eager, "{} {}", quick, eager);
+    }
+
+    #[test]
+    fn no_time_passing_changes_nothing() {
+        for filter in [ONE_EURO, EMA] {
+            let mut state = None;
+            let mut values = [0.0, 0.0];
+            filter.apply(&mut state, 1.0 / 30.0, &mut values);
+
+            let mut values = [1.0, 1.0];
+            filter.apply(&mut state, 1.0 / 30.0, &mut values);
+            let before = values;
+
+            // a repeated or out of order report gets the last value, and doesn't move it on
+            let mut values = [5.0, 5.0];
+            filter.apply(&mut state, 0.0, &mut values);
+            assert_eq!(values, before);
+            let mut values = [5.0, 5.0];
+            filter.apply(&mut state, -1.0, &mut values);
+            assert_eq!(values, before);
+            assert_eq!(state.as_ref().unwrap().value(), before);
+        }
+    }
+}
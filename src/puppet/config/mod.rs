@@ -1,18 +1,28 @@
+use self::diagnostics::{closest, find_channel, find_key, find_layer_key};
+pub use self::diagnostics::{Diagnostic, Diagnostics};
+pub use self::filters::{FilterConfig, FilterState};
 pub use self::rules::Rule;
-use self::rules::{FollowQuatRule, FollowVec2Rule, ThreeDimensions, ThresholdRule};
-use crate::tracker::TrackingReport;
+use self::rules::{
+    FollowFacingPointRule, FollowFloatRule, FollowQuatRule, FollowVec2Rule, RotateWithRule,
+    ThreeDimensions, ThresholdRule,
+};
+use super::error::RigError;
+use crate::tracker::{Channels, FieldKind, TrackingReport};
 use core::ops::Mul;
 use serde::Deserialize;
 use serde_yaml::from_str;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{io, io::Read};
-use three_d::{Mat4, SquareMatrix};
+use three_d::{Mat4, Vec3};
 
+mod diagnostics;
+mod filters;
 mod rules;
 
-#[derive(Debug, Deserialize, Copy, Clone)]
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LayerConfig {
     #[serde(default = "default_visible")]
     pub visible: bool,
@@ -31,22 +41,82 @@ pub struct LayerConfig {
 
     /// move absolutely in a direction
     pub follow: Option<FollowVec2Rule>,
+
+    /// move horizontally with a source
+    pub follow_x: Option<FollowFloatRule>,
+
+    /// move vertically with a source
+    pub follow_y: Option<FollowFloatRule>,
+
+    /// move towards the point that a rotation source is facing
+    pub follow_facing_point: Option<FollowFacingPointRule>,
+
+    /// rotate flat in the plane of the image with the roll of a source
+    pub rotate_with: Option<RotateWithRule>,
 }
 
 impl LayerConfig {
     /// the transformation that this layer should have applied
     pub fn transform(&self, report: &TrackingReport) -> Mat4 {
-        let mut transformation = match self.follow {
-            Some(follow) => Mat4::from_translation(follow.apply(report)),
-            None => Mat4::identity(),
-        };
+        let mut translation = Vec3::new(0.0, 0.0, 0.0);
+
+        if let Some(follow) = &self.follow {
+            translation += follow.apply(report);
+        }
+        if let Some(follow_x) = &self.follow_x {
+            translation.x += follow_x.apply(report);
+        }
+        if let Some(follow_y) = &self.follow_y {
+            translation.y += follow_y.apply(report);
+        }
+        if let Some(follow_facing_point) = &self.follow_facing_point {
+            translation += follow_facing_point.apply(report);
+        }
 
-        if let Some(rotate_3d) = self.rotate_3d {
+        let mut transformation = Mat4::from_translation(translation);
+
+        if let Some(rotate_with) = &self.rotate_with {
+            transformation = rotate_with.apply(report).mul(transformation);
+        }
+
+        if let Some(rotate_3d) = &self.rotate_3d {
             transformation = rotate_3d.apply(report).mul(transformation);
         }
 
         transformation
     }
+
+    /// every channel this config reads, and the kind of value it expects in each
+    pub fn channels(&self) -> Vec<(&str, FieldKind)> {
+        let mut channels = Vec::new();
+
+        if let Some(rule) = &self.visible_when {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.invisible_when {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.rotate_3d {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.follow {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.follow_x {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.follow_y {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.follow_facing_point {
+            channels.push(rule.channel());
+        }
+        if let Some(rule) = &self.rotate_with {
+            channels.push(rule.channel());
+        }
+
+        channels
+    }
 }
 
 impl Default for LayerConfig {
@@ -59,21 +129,269 @@ fn default_visible() -> bool {
     true
 }
 
+/// how to fill in the frames between tracking reports
+#[derive(Debug, Deserialize, Copy, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InterpolationConfig {
+    /// blend between the last two reports instead of jumping from one to the next. this delays
+    /// the puppet by about one report
+    #[serde(default = "default_interpolate")]
+    pub enabled: bool,
+
+    /// how many seconds to keep moving in the same direction past the latest report when the
+    /// next one is late
+    #[serde(default)]
+    pub extrapolate: f32,
+}
+
+impl Default for InterpolationConfig {
+    fn default() -> InterpolationConfig {
+        from_str("{}").unwrap()
+    }
+}
+
+fn default_interpolate() -> bool {
+    true
+}
+
+/// what to do with the puppet when the tracker can't see a face
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TrackingLostConfig {
+    /// how many seconds to go without a face before deciding it's gone, so that the odd missed
+    /// frame doesn't count
+    #[serde(default = "default_lost_after")]
+    pub after: f32,
+
+    /// the pose to take while the face is gone
+    #[serde(default)]
+    pub pose: LostPose,
+
+    /// how many seconds to take getting into that pose, and getting back out of it when the face
+    /// comes back
+    #[serde(default = "default_lost_ease")]
+    pub ease: f32,
+
+    /// layers or stacks, like an "away" sign, to draw instead of everything else while the face
+    /// is gone. they're hidden the rest of the time
+    #[serde(default)]
+    pub show: Vec<String>,
+}
+
+impl Default for TrackingLostConfig {
+    fn default() -> TrackingLostConfig {
+        from_str("{}").unwrap()
+    }
+}
+
+fn default_lost_after() -> f32 {
+    0.5
+}
+
+fn default_lost_ease() -> f32 {
+    0.3
+}
+
+#[derive(Debug, Deserialize, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LostPose {
+    /// stay in the last pose the tracker saw
+    #[default]
+    Hold,
+
+    /// go back to rest
+    Neutral,
+}
+
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub layers: HashMap<String, LayerConfig>,
+
+    #[serde(default)]
+    pub interpolation: InterpolationConfig,
+
+    /// channels this rig's tracker reports besides the standard ones
+    #[serde(default)]
+    pub channels: Channels,
+
+    /// smoothing to apply to channels before any layer sees them
+    #[serde(default)]
+    pub filters: HashMap<String, FilterConfig>,
+
+    /// what to do when the tracker can't see a face
+    #[serde(default)]
+    pub tracking_lost: TrackingLostConfig,
+
+    #[serde(skip)]
+    path: PathBuf,
+
+    #[serde(skip)]
+    source: String,
+}
+
+impl Config {
+    /// complain about any layer configs, or layers to show when tracking is lost, that don't
+    /// refer to a layer or stack in the rig, since they're probably typos
+    pub fn check_layer_names<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), RigError> {
+        let names: HashSet<&str> = names.into_iter().collect();
+        let mut diagnostics: Vec<Diagnostic> = self
+            .layers
+            .keys()
+            .filter(|key| !names.contains(key.as_str()))
+            .map(|key| Diagnostic {
+                position: find_layer_key(&self.source, key),
+                message: format!("there is no layer or stack called `{}` in the rig", key),
+            })
+            .collect();
+
+        diagnostics.extend(
+            self.tracking_lost
+                .show
+                .iter()
+                .filter(|name| !names.contains(name.as_str()))
+                .map(|name| Diagnostic {
+                    position: find_channel(&self.source, name),
+                    message: format!("there is no layer or stack called `{}` to show", name),
+                }),
+        );
+
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+
+        diagnostics.sort_by_key(|d| d.position);
+        Err(RigError::Config(Diagnostics {
+            path: self.path.clone(),
+            diagnostics,
+        }))
+    }
+
+    /// complain about any channels that layers or filters refer to that no tracker is expected to
+    /// report, or that are used as the wrong kind of value
+    pub fn check_channels(&self) -> Result<(), RigError> {
+        let mut known = Channels::standard();
+        let mut diagnostics = Vec::new();
+
+        for (name, kind) in self.channels.iter() {
+            match known.kind_of(name) {
+                Some(k) if k != kind => diagnostics.push(Diagnostic {
+                    position: find_channel(&self.source, name),
+                    message: format!("`{}` is already a {} channel", name, k),
+                }),
+                _ => known.insert(name, kind),
+            }
+        }
+
+        let names: Vec<&str> = known.iter().map(|(name, _)| name).collect();
+        let unknown = |name: &str| Diagnostic {
+            position: find_channel(&self.source, name),
+            message: match closest(name, names.iter().copied()) {
+                Some(suggestion) => format!(
+                    "there is no channel called `{}` (did you mean `{}`?)",
+                    name, suggestion
+                ),
+                None => format!(
+                    "there is no channel called `{}`; if your tracker reports it, add it to \
+                     `channels`",
+                    name
+                ),
+            },
+        };
+
+        for layer in self.layers.values() {
+            for (name, kind) in layer.channels() {
+                match known.kind_of(name) {
+                    Some(k) if k == kind => (),
+                    Some(k) => diagnostics.push(Diagnostic {
+                        position: find_channel(&self.source, name),
+                        message: format!("`{}` is a {} channel, not a {}", name, k, kind),
+                    }),
+                    None => diagnostics.push(unknown(name)),
+                }
+            }
+        }
+
+        for name in self.filters.keys() {
+            if known.kind_of(name).is_none() {
+                diagnostics.push(unknown(name));
+            }
+        }
+
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+
+        diagnostics.sort_by_key(|d| d.position);
+        diagnostics.dedup_by(|a, b| a.position == b.position && a.message == b.message);
+        Err(RigError::Config(Diagnostics {
+            path: self.path.clone(),
+            diagnostics,
+        }))
+    }
+
+    /// complain about tracking loss timings that we couldn't wait for
+    pub fn check_tracking_lost(&self) -> Result<(), RigError> {
+        let timings = [
+            ("after", self.tracking_lost.after),
+            ("ease", self.tracking_lost.ease),
+        ];
+        let diagnostics: Vec<Diagnostic> = timings
+            .iter()
+            .filter(|(_, seconds)| !seconds.is_finite() || *seconds < 0.0)
+            .map(|(name, seconds)| Diagnostic {
+                position: find_key(&self.source, "tracking_lost", name),
+                message: format!(
+                    "`tracking_lost.{}` should be a number of seconds, not {}",
+                    name, seconds
+                ),
+            })
+            .collect();
+
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+
+        Err(RigError::Config(Diagnostics {
+            path: self.path.clone(),
+            diagnostics,
+        }))
+    }
 }
 
-pub fn load(ora_path: &Path) -> io::Result<Config> {
+/// the path of the config that goes with the .ora file at `ora_path`
+pub fn path_for(ora_path: &Path) -> Result<PathBuf, RigError> {
+    match ora_path.file_name() {
+        Some(f) => Ok(ora_path.with_file_name(format!("{}.layertuber.yaml", f.to_string_lossy()))),
+        None => Err(RigError::NoFileName(ora_path.to_path_buf())),
+    }
+}
+
+pub fn load(ora_path: &Path) -> Result<Config, RigError> {
     let mut config_string = String::new();
-    let config_path = ora_path.with_file_name(format!(
-        "{}.layertuber.yaml",
-        match ora_path.file_name() {
-            Some(f) => f.to_string_lossy(),
-            None => panic!("no filename for {}", ora_path.display()),
-        }
-    ));
-    let mut config_file = File::open(config_path)?;
+    let config_path = path_for(ora_path)?;
+    let mut config_file = match File::open(&config_path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(RigError::MissingConfig(config_path))
+        }
+        Err(e) => return Err(e.into()),
+    };
     config_file.read_to_string(&mut config_string)?;
-    Ok(from_str(&config_string).expect("this should be a question mark"))
+
+    let mut config: Config = from_str(&config_string).map_err(|e| {
+        RigError::Config(Diagnostics {
+            path: config_path.clone(),
+            diagnostics: vec![e.into()],
+        })
+    })?;
+
+    config.path = config_path;
+    config.source = config_string;
+    config.check_channels()?;
+    config.check_tracking_lost()?;
+    Ok(config)
 }
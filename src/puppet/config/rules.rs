@@ -1,8 +1,12 @@
-use crate::tracker::{FloatSource, QuatSource, Source, TrackingReport, Vec2Source};
-use serde::Deserialize;
-use three_d::{Mat4, Quaternion, Vec3};
+use crate::tracker::{FieldKind, FloatSource, QuatSource, Source, TrackingReport, Vec2Source};
+use core::ops::Mul;
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use three_d::{Mat4, Quaternion, Rad, Rotation, Vec3};
 
 #[derive(Debug, Deserialize, Copy, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ThreeDimensions {
     x: Option<f32>,
     y: Option<f32>,
@@ -19,6 +23,56 @@ impl From<ThreeDimensions> for Vec3 {
     }
 }
 
+/// a scale that can be given either as one number for every axis, or per axis. per axis scales
+/// are read as `ThreeDimensions`, so that mistakes in them are reported as such, rather than as
+/// the scale being neither one thing nor the other
+#[derive(Debug, Copy, Clone)]
+pub struct Scale(Vec3);
+
+impl<'de> Deserialize<'de> for Scale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Scale, D::Error> {
+        struct ScaleVisitor;
+
+        impl<'de> Visitor<'de> for ScaleVisitor {
+            type Value = Scale;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a number, or a mapping of x, y and z")
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Scale, E> {
+                let v = v as f32;
+                Ok(Scale(Vec3 { x: v, y: v, z: v }))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Scale, E> {
+                self.visit_f64(v as f64)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Scale, E> {
+                self.visit_f64(v as f64)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Scale, A::Error> {
+                let axes = ThreeDimensions::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(Scale(axes.into()))
+            }
+        }
+
+        deserializer.deserialize_any(ScaleVisitor)
+    }
+}
+
+impl From<Scale> for Vec3 {
+    fn from(s: Scale) -> Self {
+        s.0
+    }
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
 const IDENTITY_QUAT: Quaternion<f32> = Quaternion {
     v: Vec3 {
         x: 0.0,
@@ -30,9 +84,13 @@ const IDENTITY_QUAT: Quaternion<f32> = Quaternion {
 
 pub trait Rule<T> {
     fn apply(&self, report: &TrackingReport) -> T;
+
+    /// the channel this rule reads, and the kind of value it expects there
+    fn channel(&self) -> (&str, FieldKind);
 }
 
-#[derive(Debug, Deserialize, Copy, Clone)]
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ThresholdRule {
     source: FloatSource,
     greater_than: f32,
@@ -42,12 +100,17 @@ impl Rule<bool> for ThresholdRule {
     fn apply(&self, report: &TrackingReport) -> bool {
         self.source.value(report) > self.greater_than
     }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        self.source.channel()
+    }
 }
 
-#[derive(Debug, Deserialize, Copy, Clone)]
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct FollowVec2Rule {
     source: Vec2Source,
-    scale: ThreeDimensions,
+    scale: Scale,
 }
 
 impl Rule<Vec3> for FollowVec2Rule {
@@ -59,11 +122,18 @@ impl Rule<Vec3> for FollowVec2Rule {
             z: 0.0,
         }
     }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        self.source.channel()
+    }
 }
 
-#[derive(Debug, Deserialize, Copy, Clone)]
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct FollowQuatRule {
     source: QuatSource,
+
+    #[serde(default = "default_scale")]
     scale: f32,
 }
 
@@ -72,4 +142,75 @@ impl Rule<Mat4> for FollowQuatRule {
         let quat = self.source.value(report);
         IDENTITY_QUAT.slerp(quat, self.scale).into()
     }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        self.source.channel()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FollowFloatRule {
+    source: FloatSource,
+
+    #[serde(default = "default_scale")]
+    scale: f32,
+}
+
+impl Rule<f32> for FollowFloatRule {
+    fn apply(&self, report: &TrackingReport) -> f32 {
+        self.source.value(report) * self.scale
+    }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        self.source.channel()
+    }
+}
+
+/// rotate flat within the plane of the image, following only the roll of a rotation
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RotateWithRule {
+    source: QuatSource,
+
+    #[serde(default = "default_scale")]
+    scale: f32,
+}
+
+impl Rule<Mat4> for RotateWithRule {
+    fn apply(&self, report: &TrackingReport) -> Mat4 {
+        let right = self.source.value(report).rotate_vector(Vec3::unit_x());
+        Mat4::from_angle_z(Rad(right.y.atan2(right.x) * self.scale))
+    }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        self.source.channel()
+    }
+}
+
+/// move towards the point that a rotation is facing, so that a flat layer can give the
+/// impression of turning without actually rotating in 3d
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FollowFacingPointRule {
+    source: QuatSource,
+
+    #[serde(default = "default_scale")]
+    scale: f32,
+}
+
+impl Rule<Vec3> for FollowFacingPointRule {
+    fn apply(&self, report: &TrackingReport) -> Vec3 {
+        let forward = self.source.value(report).rotate_vector(Vec3::unit_z());
+        Vec3 {
+            x: forward.x,
+            y: forward.y,
+            z: 0.0,
+        }
+        .mul(self.scale)
+    }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        self.source.channel()
+    }
 }
@@ -79,3 +79,63 @@ pub fn from_asset(asset: three_d_asset::Texture2D) -> three_d::CpuTexture {
         wrap_t: wrapping(asset.wrap_t),
     }
 }
+
+fn rgba_u8(pixels: impl Iterator<Item = [u8; 4]>, opacity: f32) -> three_d::TextureData {
+    three_d::TextureData::RgbaU8(
+        pixels
+            .map(|p| {
+                let alpha = p[3] as f32 / 255.0 * opacity;
+                let scale = |c: u8| (c as f32 * alpha).round() as u8;
+                [
+                    scale(p[0]),
+                    scale(p[1]),
+                    scale(p[2]),
+                    (alpha * 255.0).round() as u8,
+                ]
+            })
+            .collect(),
+    )
+}
+
+fn rgba_f32(pixels: impl Iterator<Item = [f32; 4]>, opacity: f32) -> three_d::TextureData {
+    three_d::TextureData::RgbaF32(
+        pixels
+            .map(|p| {
+                let alpha = p[3] * opacity;
+                [p[0] * alpha, p[1] * alpha, p[2] * alpha, alpha]
+            })
+            .collect(),
+    )
+}
+
+/// a copy of `texture` with its alpha scaled by `opacity`, and its colour multiplied by that
+/// alpha. textures without an alpha channel are treated as opaque, like OpenGL would
+pub fn premultiplied(texture: &three_d::CpuTexture, opacity: f32) -> three_d::CpuTexture {
+    use three_d::TextureData::*;
+
+    let f = |h: &three_d::f16| h.to_f32();
+
+    three_d::CpuTexture {
+        data: match &texture.data {
+            RU8(d) => rgba_u8(d.iter().map(|p| [*p, 0, 0, 255]), opacity),
+            RgU8(d) => rgba_u8(d.iter().map(|p| [p[0], p[1], 0, 255]), opacity),
+            RgbU8(d) => rgba_u8(d.iter().map(|p| [p[0], p[1], p[2], 255]), opacity),
+            RgbaU8(d) => rgba_u8(d.iter().copied(), opacity),
+            RF16(d) => rgba_f32(d.iter().map(|p| [f(p), 0.0, 0.0, 1.0]), opacity),
+            RgF16(d) => rgba_f32(d.iter().map(|p| [f(&p[0]), f(&p[1]), 0.0, 1.0]), opacity),
+            RgbF16(d) => rgba_f32(
+                d.iter().map(|p| [f(&p[0]), f(&p[1]), f(&p[2]), 1.0]),
+                opacity,
+            ),
+            RgbaF16(d) => rgba_f32(
+                d.iter().map(|p| [f(&p[0]), f(&p[1]), f(&p[2]), f(&p[3])]),
+                opacity,
+            ),
+            RF32(d) => rgba_f32(d.iter().map(|p| [*p, 0.0, 0.0, 1.0]), opacity),
+            RgF32(d) => rgba_f32(d.iter().map(|p| [p[0], p[1], 0.0, 1.0]), opacity),
+            RgbF32(d) => rgba_f32(d.iter().map(|p| [p[0], p[1], p[2], 1.0]), opacity),
+            RgbaF32(d) => rgba_f32(d.iter().copied(), opacity),
+        },
+        ..texture.clone()
+    }
+}
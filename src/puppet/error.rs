@@ -0,0 +1,71 @@
+use super::config::Diagnostics;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use zip::result::ZipError;
+
+/// Everything that can go wrong when loading a rig
+#[derive(Debug)]
+pub enum RigError {
+    Io(io::Error),
+
+    /// the .ora file is not a zip archive we can read
+    Zip(ZipError),
+
+    /// we were given a path with no file name, so we can't find its config
+    NoFileName(PathBuf),
+
+    /// there is no .ora.layertuber.yaml alongside the .ora file
+    MissingConfig(PathBuf),
+
+    /// the config could not be parsed, or does not fit the rig
+    Config(Diagnostics),
+
+    /// the .ora file claims to be something other than OpenRaster
+    WrongMimetype(String),
+
+    /// stack.xml is missing or not what we expect
+    MalformedStack(String),
+
+    /// stack.xml refers to a layer image that isn't in the .ora file
+    MissingLayer(String),
+
+    /// a layer image is in the .ora file, but we couldn't decode it
+    UndecodableTexture(String, three_d_asset::Error),
+}
+
+impl fmt::Display for RigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RigError::Io(e) => write!(f, "could not read rig: {}", e),
+            RigError::Zip(e) => write!(f, "could not read the .ora file: {}", e),
+            RigError::NoFileName(p) => write!(f, "{} is not a file", p.display()),
+            RigError::MissingConfig(p) => write!(f, "config file {} does not exist", p.display()),
+            RigError::Config(d) => d.fmt(f),
+            RigError::WrongMimetype(m) => write!(
+                f,
+                "this does not appear to be an OpenRaster file (its mimetype is {:?})",
+                m
+            ),
+            RigError::MalformedStack(e) => write!(f, "could not understand stack.xml: {}", e),
+            RigError::MissingLayer(src) => write!(f, "layer image {} is missing", src),
+            RigError::UndecodableTexture(src, e) => {
+                write!(f, "could not decode layer image {}: {}", src, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RigError {}
+
+impl From<io::Error> for RigError {
+    fn from(err: io::Error) -> RigError {
+        RigError::Io(err)
+    }
+}
+
+impl From<ZipError> for RigError {
+    fn from(err: ZipError) -> RigError {
+        RigError::Zip(err)
+    }
+}
@@ -0,0 +1,78 @@
+//! smoothing jittery sources before they reach any rules
+
+use super::config::{FilterConfig, FilterState};
+use crate::tracker::{StampedReport, TrackingReport};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Filters each incoming report according to a rig's config, remembering what it needs to
+/// between reports
+pub struct ReportFilter {
+    filters: Vec<(String, FilterConfig, Option<FilterState>)>,
+    last_received: Option<Instant>,
+}
+
+impl ReportFilter {
+    pub fn new(configs: &HashMap<String, FilterConfig>) -> ReportFilter {
+        ReportFilter {
+            filters: configs
+                .iter()
+                .map(|(name, config)| (name.clone(), *config, None))
+                .collect(),
+            last_received: None,
+        }
+    }
+
+    pub fn apply(&mut self, mut stamped: StampedReport) -> StampedReport {
+        let dt = match self.last_received {
+            Some(last) => stamped.received.duration_since(last).as_secs_f32(),
+            None => 0.0,
+        };
+        self.last_received = Some(stamped.received);
+
+        for (name, config, state) in &mut self.filters {
+            let (values, rotation) = match components_mut(&mut stamped.report, name) {
+                Some(found) => found,
+                None => continue,
+            };
+
+            if let (true, Some(state)) = (rotation, state.as_ref()) {
+                // q and -q are the same rotation, but averaging them gives nonsense
+                let dot: f32 = state
+                    .value()
+                    .iter()
+                    .zip(values.iter())
+                    .map(|(a, b)| a * b)
+                    .sum();
+                if dot < 0.0 {
+                    values.iter_mut().for_each(|v| *v = -*v);
+                }
+            }
+
+            config.apply(state, dt, values);
+
+            if rotation {
+                let length = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+                if length > 0.0 {
+                    values.iter_mut().for_each(|v| *v /= length);
+                }
+            }
+        }
+
+        stamped
+    }
+}
+
+/// the values of the channel called `name`, and whether they make up a rotation
+fn components_mut<'a>(report: &'a mut TrackingReport, name: &str) -> Option<(&'a mut [f32], bool)> {
+    if let Some(value) = report.floats.get_mut(name) {
+        Some((std::slice::from_mut(value), false))
+    } else if let Some(value) = report.vec2s.get_mut(name) {
+        Some((value, false))
+    } else {
+        report
+            .rotations
+            .get_mut(name)
+            .map(|value| (&mut value[..], true))
+    }
+}
@@ -0,0 +1,202 @@
+//! smoothing out the steps between tracking reports, which usually arrive less often than frames
+//! are drawn
+
+use super::config::InterpolationConfig;
+use crate::tracker::{StampedReport, TrackingReport};
+use std::time::{Duration, Instant};
+
+/// if reports are further apart than this, the tracker probably stalled, and gliding slowly
+/// between them would look worse than just jumping
+const MAX_GAP: Duration = Duration::from_millis(250);
+
+/// Keeps the two most recent reports, and works out the pose to draw in between
+pub struct Interpolator {
+    config: InterpolationConfig,
+    previous: Option<StampedReport>,
+    latest: Option<StampedReport>,
+}
+
+impl Interpolator {
+    pub fn new(config: InterpolationConfig) -> Interpolator {
+        Interpolator {
+            config,
+            previous: None,
+            latest: None,
+        }
+    }
+
+    pub fn set_config(&mut self, config: InterpolationConfig) {
+        self.config = config;
+    }
+
+    pub fn push(&mut self, report: StampedReport) {
+        self.previous = self.latest.replace(report);
+    }
+
+    /// the pose to draw at `now`
+    pub fn report_at(&self, now: Instant) -> TrackingReport {
+        let latest = match &self.latest {
+            Some(l) => l,
+            None => return TrackingReport::default(),
+        };
+
+        let previous = match &self.previous {
+            Some(p) if self.config.enabled => p,
+            _ => return latest.report.clone(),
+        };
+
+        let gap = latest.received.duration_since(previous.received);
+        if gap.is_zero() || gap > MAX_GAP {
+            return latest.report.clone();
+        }
+
+        // we're always one report behind, so the time since the latest report arrived tells us
+        // how far from the previous report to the latest one we should be
+        let gap = gap.as_secs_f32();
+        let since = now.duration_since(latest.received).as_secs_f32();
+        let t = (since / gap).min(1.0 + self.config.extrapolate.max(0.0) / gap);
+
+        previous.report.interpolate(&latest.report, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn config(enabled: bool, extrapolate: f32) -> InterpolationConfig {
+        InterpolationConfig {
+            enabled,
+            extrapolate,
+        }
+    }
+
+    fn at(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    fn mouth(received: Instant, mouth_open: f32) -> StampedReport {
+        let mut report = TrackingReport::default();
+        report.floats.insert("mouth_open".to_string(), mouth_open);
+        StampedReport { received, report }
+    }
+
+    /// reports with an open mouth at 0 and 100ms, going from closed to open
+    fn opening(config: InterpolationConfig, start: Instant) -> Interpolator {
+        let mut interpolator = Interpolator::new(config);
+        interpolator.push(mouth(start, 0.0));
+        interpolator.push(mouth(at(start, 100), 1.0));
+        interpolator
+    }
+
+    fn mouth_at(interpolator: &Interpolator, now: Instant) -> f32 {
+        interpolator.report_at(now).floats["mouth_open"]
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn the_first_report_is_drawn_as_it_is() {
+        let start = Instant::now();
+        let mut interpolator = Interpolator::new(config(true, 0.0));
+        assert_eq!(interpolator.report_at(start), TrackingReport::default());
+
+        interpolator.push(mouth(start, 0.5));
+        assert_eq!(mouth_at(&interpolator, start), 0.5);
+        assert_eq!(mouth_at(&interpolator, at(start, 1000)), 0.5);
+    }
+
+    #[test]
+    fn floats_are_blended_between_reports() {
+        let start = Instant::now();
+        let interpolator = opening(config(true, 0.0), start);
+
+        // one report behind: the previous report when the latest arrives, and the latest one
+        // when the next one should
+        assert!(close(mouth_at(&interpolator, at(start, 100)), 0.0));
+        assert!(close(mouth_at(&interpolator, at(start, 150)), 0.5));
+        assert!(close(mouth_at(&interpolator, at(start, 200)), 1.0));
+    }
+
+    #[test]
+    fn rotations_are_slerped_between_reports() {
+        let start = Instant::now();
+        let turned = |received, rotation| {
+            let mut report = TrackingReport::default();
+            report
+                .rotations
+                .insert("head_rotation".to_string(), rotation);
+            StampedReport { received, report }
+        };
+
+        let mut interpolator = Interpolator::new(config(true, 0.0));
+        interpolator.push(turned(start, [0.0, 0.0, 0.0, 1.0]));
+
+        // a quarter turn about z, which halfway through is an eighth of a turn
+        interpolator.push(turned(
+            at(start, 100),
+            [0.0, 0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+        ));
+        let rotation = interpolator.report_at(at(start, 150)).rotations["head_rotation"];
+        let expected = [0.0, 0.0, 0.3827, 0.9239];
+        assert!(
+            rotation.iter().zip(expected).all(|(a, b)| close(*a, b)),
+            "{:?}",
+            rotation
+        );
+    }
+
+    #[test]
+    fn extrapolation_is_clamped() {
+        let start = Instant::now();
+
+        let interpolator = opening(config(true, 0.0), start);
+        assert!(close(mouth_at(&interpolator, at(start, 300)), 1.0));
+
+        // 50ms past the latest report is half a gap further
+        let interpolator = opening(config(true, 0.05), start);
+        assert!(close(mouth_at(&interpolator, at(start, 250)), 1.5));
+        assert!(close(mouth_at(&interpolator, at(start, 1000)), 1.5));
+
+        // and extrapolating backwards is the same as not extrapolating at all
+        let interpolator = opening(config(true, -1.0), start);
+        assert!(close(mouth_at(&interpolator, at(start, 300)), 1.0));
+    }
+
+    #[test]
+    fn channels_in_one_report_are_not_blended() {
+        let start = Instant::now();
+        let mut interpolator = Interpolator::new(config(true, 0.0));
+
+        let mut previous = mouth(start, 0.0);
+        previous.report.floats.insert("blink".to_string(), 0.0);
+        interpolator.push(previous);
+
+        let mut latest = mouth(at(start, 100), 1.0);
+        latest.report.floats.insert("brows".to_string(), 0.5);
+        interpolator.push(latest);
+
+        // channels that only just turned up are taken as they are, and ones that went away stay
+        // gone
+        let report = interpolator.report_at(at(start, 150));
+        assert!(close(report.floats["mouth_open"], 0.5));
+        assert_eq!(report.floats["brows"], 0.5);
+        assert!(!report.floats.contains_key("blink"));
+    }
+
+    #[test]
+    fn stalls_and_disabled_interpolation_jump_to_the_latest_report() {
+        let start = Instant::now();
+
+        let interpolator = opening(config(false, 0.0), start);
+        assert_eq!(mouth_at(&interpolator, at(start, 100)), 1.0);
+
+        let mut interpolator = Interpolator::new(config(true, 0.0));
+        interpolator.push(mouth(start, 0.0));
+        interpolator.push(mouth(at(start, 1000), 1.0));
+        assert_eq!(mouth_at(&interpolator, at(start, 1000)), 1.0);
+    }
+}
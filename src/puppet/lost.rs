@@ -0,0 +1,211 @@
+//! noticing when the tracker has lost sight of the face, and easing into and out of the pose the
+//! rig wants to take while it's gone
+
+use super::config::{LostPose, TrackingLostConfig};
+use crate::tracker::{TrackingReport, FACE_FOUND};
+use log::info;
+use std::time::{Duration, Instant};
+
+/// Keeps track of when the face was last seen, and works out the pose to draw
+pub struct TrackingLoss {
+    config: TrackingLostConfig,
+    last_found: Instant,
+    lost: bool,
+
+    /// when the face was last lost or found, and the pose we were in then, to ease away from
+    changed: Instant,
+    from: TrackingReport,
+
+    /// the pose we last drew
+    current: TrackingReport,
+}
+
+impl TrackingLoss {
+    /// start out as if the face was seen at `now`, so that it counts as lost if the tracker never
+    /// finds it
+    pub fn new(config: TrackingLostConfig, now: Instant) -> TrackingLoss {
+        TrackingLoss {
+            config,
+            last_found: now,
+            lost: false,
+            changed: now,
+            from: TrackingReport::default(),
+            current: TrackingReport::default(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: TrackingLostConfig) {
+        self.config = config;
+    }
+
+    /// note that the tracker saw a face in a report it made at `received`
+    pub fn found(&mut self, received: Instant) {
+        self.last_found = self.last_found.max(received);
+    }
+
+    /// the pose to draw at `now`, given the pose tracking says the puppet should be in. the
+    /// result's `face_found` eases between 0 and 1 along with it, for rules that care
+    pub fn report_at(&mut self, now: Instant, tracked: TrackingReport) -> TrackingReport {
+        let after = Duration::from_secs_f32(self.config.after.max(0.0));
+        let lost = now.saturating_duration_since(self.last_found) > after;
+
+        if lost != self.lost {
+            if lost {
+                info!("lost the face");
+            } else {
+                info!("found the face again");
+            }
+
+            self.lost = lost;
+            self.changed = now;
+            self.from = self.current.clone();
+        }
+
+        let (mut target, found) = match (self.lost, self.config.pose) {
+            (false, _) => (tracked, 1.0),
+            (true, LostPose::Hold) => (self.from.clone(), 0.0),
+            (true, LostPose::Neutral) => (self.from.resting(), 0.0),
+        };
+        target.floats.insert(FACE_FOUND.to_string(), found);
+
+        let since = now.saturating_duration_since(self.changed).as_secs_f32();
+        let t = if self.config.ease > 0.0 {
+            (since / self.config.ease).min(1.0)
+        } else {
+            1.0
+        };
+
+        // ease in and out, rather than starting and stopping suddenly
+        let report = self.from.interpolate(&target, t * t * (3.0 - 2.0 * t));
+        self.current = report.clone();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pose: LostPose, after: f32, ease: f32) -> TrackingLostConfig {
+        TrackingLostConfig {
+            after,
+            pose,
+            ease,
+            show: Vec::new(),
+        }
+    }
+
+    fn mouth(mouth_open: f32) -> TrackingReport {
+        let mut report = TrackingReport::default();
+        report.floats.insert("mouth_open".to_string(), mouth_open);
+        report
+    }
+
+    fn at(start: Instant, seconds: f32) -> Instant {
+        start + Duration::from_secs_f32(seconds)
+    }
+
+    /// `(mouth_open, face_found)` in `report`
+    fn pose(report: &TrackingReport) -> (f32, f32) {
+        (report.floats["mouth_open"], report.floats[FACE_FOUND])
+    }
+
+    fn close(actual: (f32, f32), expected: (f32, f32)) -> bool {
+        (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3
+    }
+
+    /// a `TrackingLoss` that has been seeing an open mouth for long enough to be done easing in,
+    /// last seen at one second in
+    fn settled(config: TrackingLostConfig, start: Instant) -> TrackingLoss {
+        let mut loss = TrackingLoss::new(config, start);
+        loss.found(at(start, 1.0));
+        assert!(close(
+            pose(&loss.report_at(at(start, 1.0), mouth(1.0))),
+            (1.0, 1.0)
+        ));
+        loss
+    }
+
+    /// the pose at `now`, having seen an open mouth then
+    fn seen(loss: &mut TrackingLoss, now: Instant) -> (f32, f32) {
+        loss.found(now);
+        pose(&loss.report_at(now, mouth(1.0)))
+    }
+
+    #[test]
+    fn the_face_is_only_lost_after_a_while() {
+        let start = Instant::now();
+        let mut loss = TrackingLoss::new(config(LostPose::Hold, 0.5, 0.0), start);
+
+        // never having seen the face counts the same as having just lost it
+        let report = loss.report_at(at(start, 0.4), mouth(1.0));
+        assert!(close(pose(&report), (1.0, 1.0)), "{:?}", report);
+        let report = loss.report_at(at(start, 0.6), mouth(0.5));
+        assert!(close(pose(&report), (1.0, 0.0)), "{:?}", report);
+
+        loss.found(at(start, 0.7));
+        let report = loss.report_at(at(start, 1.1), mouth(0.5));
+        assert!(close(pose(&report), (0.5, 1.0)), "{:?}", report);
+        let report = loss.report_at(at(start, 1.3), mouth(0.5));
+        assert!(close(pose(&report), (0.5, 0.0)), "{:?}", report);
+    }
+
+    #[test]
+    fn losing_and_finding_the_face_eases_in_and_out() {
+        let start = Instant::now();
+        let mut loss = settled(config(LostPose::Neutral, 0.5, 1.0), start);
+
+        let poses: Vec<(f32, f32)> = [1.6, 2.1, 2.6]
+            .iter()
+            .map(|&seconds| pose(&loss.report_at(at(start, seconds), mouth(1.0))))
+            .collect();
+        assert!(close(poses[0], (1.0, 1.0)), "{:?}", poses);
+        assert!(close(poses[1], (0.5, 0.5)), "{:?}", poses);
+        assert!(close(poses[2], (0.0, 0.0)), "{:?}", poses);
+
+        let poses: Vec<(f32, f32)> = [2.7, 2.95, 3.2, 3.7]
+            .iter()
+            .map(|&seconds| seen(&mut loss, at(start, seconds)))
+            .collect();
+        assert!(close(poses[0], (0.0, 0.0)), "{:?}", poses);
+
+        // smoothstep is slow to start with
+        assert!(poses[1].0 > 0.0 && poses[1].0 < 0.25, "{:?}", poses);
+        assert!(close(poses[2], (0.5, 0.5)), "{:?}", poses);
+        assert!(close(poses[3], (1.0, 1.0)), "{:?}", poses);
+    }
+
+    #[test]
+    fn holding_keeps_the_last_pose() {
+        let start = Instant::now();
+        let mut loss = settled(config(LostPose::Hold, 0.5, 1.0), start);
+
+        for seconds in [1.6, 2.1, 2.6, 5.0] {
+            let report = loss.report_at(at(start, seconds), mouth(0.0));
+            assert_eq!(report.floats["mouth_open"], 1.0, "{:?}", report);
+        }
+        assert_eq!(
+            loss.report_at(at(start, 5.0), mouth(0.0)).floats[FACE_FOUND],
+            0.0
+        );
+    }
+
+    #[test]
+    fn a_face_coming_back_mid_ease_carries_on_from_where_it_was() {
+        let start = Instant::now();
+        let mut loss = settled(config(LostPose::Neutral, 0.5, 1.0), start);
+
+        loss.report_at(at(start, 1.6), mouth(1.0));
+        let report = loss.report_at(at(start, 2.1), mouth(1.0));
+        assert!(close(pose(&report), (0.5, 0.5)), "{:?}", report);
+
+        // no jumping back to the tracked pose: it eases back from halfway
+        let poses: Vec<(f32, f32)> = [2.1, 2.6, 3.1]
+            .iter()
+            .map(|&seconds| seen(&mut loss, at(start, seconds)))
+            .collect();
+        assert!(close(poses[0], (0.5, 0.5)), "{:?}", poses);
+        assert!(close(poses[1], (0.75, 0.75)), "{:?}", poses);
+        assert!(close(poses[2], (1.0, 1.0)), "{:?}", poses);
+    }
+}
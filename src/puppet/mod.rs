@@ -1,10 +1,19 @@
-pub use render::render;
+pub use error::RigError;
+pub use render::{render, Puppet};
 pub use rig::Rig;
 
+pub mod offline;
 pub mod rig;
 
 mod camera;
+mod composite;
 mod config;
 mod conv;
+mod error;
+mod filter;
+mod interpolate;
+mod lost;
 mod ora;
+mod pipeline;
 mod render;
+mod watch;
@@ -0,0 +1,140 @@
+//! rendering a puppet without a window, for making clips and for comparing against reference
+//! images
+
+use super::pipeline::ReportPipeline;
+use super::render::Puppet;
+use super::rig::Rig;
+use crate::tracker::{StampedReport, TimedReport, TrackerState, TrackingReport};
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use three_d::{
+    Context, DepthFormat, DepthTargetTexture2D, Interpolation, RenderTarget, Texture2D, Viewport,
+    Wrapping,
+};
+
+/// The pose to draw on each frame of a recording played back at `fps` frames per second, worked
+/// out just as it would be live. Made by `poses`
+pub struct Poses<'a> {
+    reports: &'a [TimedReport],
+
+    /// how far into the recording each report arrived
+    arrivals: Vec<Duration>,
+    next_report: usize,
+
+    pipeline: ReportPipeline,
+    started: Instant,
+    frame_interval: Duration,
+    frame: u32,
+}
+
+/// pose `rig` for every frame from the start of `reports` to the last of them. like a live
+/// renderer, each frame only sees the latest report to have arrived since the last one. captured
+/// tracker output has no times, so its reports are taken to arrive one per frame
+pub fn poses<'a>(rig: &Rig, reports: &'a [TimedReport], fps: u32) -> Poses<'a> {
+    let frame_interval = Duration::from_secs(1) / fps.max(1);
+    let timed = reports.iter().any(|r| r.time != 0.0);
+    let arrivals: Vec<Duration> = reports
+        .iter()
+        .enumerate()
+        .map(|(index, r)| match timed {
+            true => Duration::try_from_secs_f64(r.time).unwrap_or(Duration::ZERO),
+            false => frame_interval * index as u32,
+        })
+        .collect();
+
+    let started = Instant::now();
+    Poses {
+        reports,
+        arrivals,
+        next_report: 0,
+        pipeline: ReportPipeline::new(rig, started),
+        started,
+        frame_interval,
+        frame: 0,
+    }
+}
+
+impl Iterator for Poses<'_> {
+    type Item = TrackingReport;
+
+    fn next(&mut self) -> Option<TrackingReport> {
+        // stop after the first frame to see the last report
+        if self.next_report == self.reports.len() {
+            return None;
+        }
+
+        let since_start = self.frame_interval * self.frame;
+        self.frame += 1;
+
+        let mut latest = None;
+        while self
+            .arrivals
+            .get(self.next_report)
+            .is_some_and(|arrival| *arrival <= since_start)
+        {
+            latest = Some(self.next_report);
+            self.next_report += 1;
+        }
+
+        if let Some(index) = latest {
+            self.pipeline.push(StampedReport {
+                received: self.started + self.arrivals[index],
+                report: self.reports[index].report.clone(),
+            });
+        }
+
+        Some(self.pipeline.report_at(self.started + since_start))
+    }
+}
+
+/// render a single frame off-screen, returning RGBA pixels, row by row from the top. `tracker` is
+/// the state of the tracker the report came from; recordings come from one that's running
+pub fn render_frame(
+    context: &Context,
+    puppet: &mut Puppet,
+    width: u32,
+    height: u32,
+    report: &TrackingReport,
+    tracker: TrackerState,
+) -> Vec<[u8; 4]> {
+    let mut color = Texture2D::new_empty::<[u8; 4]>(
+        context,
+        width,
+        height,
+        Interpolation::Nearest,
+        Interpolation::Nearest,
+        None,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    );
+    let mut depth = DepthTargetTexture2D::new(
+        context,
+        width,
+        height,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+        DepthFormat::Depth32F,
+    );
+
+    let target = RenderTarget::new(color.as_color_target(None), depth.as_depth_target());
+    puppet.render(
+        &target,
+        Viewport::new_at_origo(width, height),
+        report,
+        tracker,
+    );
+    target.read_color()
+}
+
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[[u8; 4]]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels.concat())?;
+    Ok(())
+}
@@ -1,8 +1,29 @@
+use super::error::RigError;
+use log::warn;
 use serde::Deserialize;
 use serde_xml_rs::de::from_str;
-use std::io;
 use std::io::{Read, Seek};
 use zip::read::ZipArchive;
+use zip::result::ZipError;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Visible,
+    Hidden,
+}
+
+fn default_visibility() -> Visibility {
+    Visibility::Visible
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_composite_op() -> String {
+    "svg:src-over".to_string()
+}
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Layer {
@@ -11,6 +32,17 @@ pub struct Layer {
     pub x: i32,
     pub y: i32,
 
+    /// once read, this includes the opacity of every stack this layer is in
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+
+    /// once read, this is hidden if any stack this layer is in is hidden
+    #[serde(default = "default_visibility")]
+    pub visibility: Visibility,
+
+    #[serde(rename = "composite-op", default = "default_composite_op")]
+    pub composite_op: String,
+
     #[serde(skip)]
     pub parent_names: Vec<String>,
 }
@@ -20,6 +52,15 @@ struct Stack {
     #[serde(rename = "$value")]
     entries: Vec<StackEntry>,
     name: String,
+
+    #[serde(default = "default_opacity")]
+    opacity: f32,
+
+    #[serde(default = "default_visibility")]
+    visibility: Visibility,
+
+    #[serde(rename = "composite-op", default = "default_composite_op")]
+    composite_op: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,43 +82,73 @@ struct Image {
     height: u32,
 }
 
-fn layers_from_stack(parent_names: Vec<String>, entries: Vec<StackEntry>) -> Vec<Layer> {
+fn layers_from_stack(
+    parent_names: Vec<String>,
+    opacity: f32,
+    visibility: Visibility,
+    entries: Vec<StackEntry>,
+) -> Vec<Layer> {
     let mut layers: Vec<Layer> = Vec::new();
 
     for entry in entries {
         match entry {
             StackEntry::Layer(mut l) => {
                 l.parent_names = parent_names.clone();
+                l.opacity *= opacity;
+                if visibility == Visibility::Hidden {
+                    l.visibility = Visibility::Hidden;
+                }
                 layers.push(l)
             }
-            StackEntry::Stack(s) => layers.extend_from_slice(&layers_from_stack(
-                [parent_names.as_slice(), &[s.name]].concat(),
-                s.entries,
-            )),
+            StackEntry::Stack(s) => {
+                if s.composite_op != default_composite_op() {
+                    warn!(
+                        "stack {} uses {}, but only layers can have composite ops; drawing its layers normally",
+                        s.name, s.composite_op
+                    );
+                }
+
+                layers.extend_from_slice(&layers_from_stack(
+                    [parent_names.as_slice(), &[s.name]].concat(),
+                    s.opacity * opacity,
+                    match visibility {
+                        Visibility::Hidden => Visibility::Hidden,
+                        Visibility::Visible => s.visibility,
+                    },
+                    s.entries,
+                ))
+            }
         }
     }
 
     layers
 }
 
-pub fn read(ora: &mut ZipArchive<impl Read + Seek>) -> io::Result<(u32, u32, Vec<Layer>)> {
+pub fn read(ora: &mut ZipArchive<impl Read + Seek>) -> Result<(u32, u32, Vec<Layer>), RigError> {
     let mut mimetype = String::new();
-    ora.by_name("mimetype")?.read_to_string(&mut mimetype)?;
+    match ora.by_name("mimetype") {
+        Ok(mut f) => f.read_to_string(&mut mimetype)?,
+        Err(ZipError::FileNotFound) => return Err(RigError::WrongMimetype(mimetype)),
+        Err(e) => return Err(e.into()),
+    };
 
     if mimetype != "image/openraster" {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "this file does not appear to be an OpenRaster file",
-        ));
+        return Err(RigError::WrongMimetype(mimetype));
     }
 
     let mut stack_xml = String::new();
-    ora.by_name("stack.xml")?.read_to_string(&mut stack_xml)?;
-    let image: Image = from_str(&stack_xml).expect("this should probably be returned as an error");
+    match ora.by_name("stack.xml") {
+        Ok(mut f) => f.read_to_string(&mut stack_xml)?,
+        Err(ZipError::FileNotFound) => {
+            return Err(RigError::MalformedStack("it does not exist".to_string()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let image: Image = from_str(&stack_xml).map_err(|e| RigError::MalformedStack(e.to_string()))?;
 
     let mut layers = Vec::new();
 
-    for layer in layers_from_stack(vec![], image.entries) {
+    for layer in layers_from_stack(vec![], 1.0, Visibility::Visible, image.entries) {
         layers.push(layer);
     }
 
@@ -85,3 +156,83 @@ pub fn read(ora: &mut ZipArchive<impl Read + Seek>) -> io::Result<(u32, u32, Vec
 
     Ok((image.width, image.height, layers))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the layers in `stack_xml`, as `read` would find them before reversing them
+    fn layers(stack_xml: &str) -> Vec<Layer> {
+        let image: Image = from_str(stack_xml).unwrap();
+        layers_from_stack(vec![], 1.0, Visibility::Visible, image.entries)
+    }
+
+    fn layer<'a>(layers: &'a [Layer], name: &str) -> &'a Layer {
+        layers.iter().find(|l| l.name == name).unwrap()
+    }
+
+    const NESTED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<image version="0.0.3" w="100" h="100">
+  <stack name="outer" opacity="0.5">
+    <layer name="in outer" src="data/1.png" x="0" y="0" opacity="0.5"/>
+    <stack name="inner" opacity="0.5" visibility="hidden">
+      <layer name="in inner" src="data/2.png" x="0" y="0"/>
+      <stack name="innermost" visibility="visible">
+        <layer name="in innermost" src="data/3.png" x="0" y="0" opacity="0.8"/>
+      </stack>
+    </stack>
+    <stack name="shown">
+      <layer name="in shown" src="data/4.png" x="0" y="0"/>
+    </stack>
+  </stack>
+  <layer name="at the top" src="data/5.png" x="0" y="0" visibility="hidden"/>
+</image>"#;
+
+    #[test]
+    fn opacity_multiplies_through_stacks() {
+        let layers = layers(NESTED);
+        assert_eq!(layer(&layers, "in outer").opacity, 0.25);
+        assert_eq!(layer(&layers, "in inner").opacity, 0.25);
+        assert_eq!(layer(&layers, "in innermost").opacity, 0.2);
+        assert_eq!(layer(&layers, "in shown").opacity, 0.5);
+        assert_eq!(layer(&layers, "at the top").opacity, 1.0);
+    }
+
+    #[test]
+    fn hidden_stacks_hide_everything_in_them() {
+        let layers = layers(NESTED);
+        assert_eq!(layer(&layers, "in outer").visibility, Visibility::Visible);
+        assert_eq!(layer(&layers, "in inner").visibility, Visibility::Hidden);
+
+        // even in stacks that are visible themselves
+        assert_eq!(
+            layer(&layers, "in innermost").visibility,
+            Visibility::Hidden
+        );
+        assert_eq!(layer(&layers, "in shown").visibility, Visibility::Visible);
+        assert_eq!(layer(&layers, "at the top").visibility, Visibility::Hidden);
+    }
+
+    #[test]
+    fn layers_know_which_stacks_they_are_in() {
+        let layers = layers(NESTED);
+        assert_eq!(
+            layer(&layers, "in innermost").parent_names,
+            ["outer", "inner", "innermost"]
+        );
+        assert!(layer(&layers, "at the top").parent_names.is_empty());
+
+        // in the order they're stacked, top first
+        let names: Vec<&str> = layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "in outer",
+                "in inner",
+                "in innermost",
+                "in shown",
+                "at the top"
+            ]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! everything that happens to tracking reports between the tracker and the puppet: smoothing
+//! them, filling in the frames between them, and easing into the rig's pose for when the face is
+//! lost
+
+use super::filter::ReportFilter;
+use super::interpolate::Interpolator;
+use super::lost::TrackingLoss;
+use super::rig::Rig;
+use crate::tracker::{StampedReport, TrackingReport};
+use std::time::Instant;
+
+/// Turns the reports a tracker makes into the pose to draw on each frame, the same way whether
+/// the puppet is drawn live or from a recording
+pub struct ReportPipeline {
+    filter: ReportFilter,
+    interpolator: Interpolator,
+    loss: TrackingLoss,
+}
+
+impl ReportPipeline {
+    /// start out as if the face was last seen at `now`
+    pub fn new(rig: &Rig, now: Instant) -> ReportPipeline {
+        ReportPipeline {
+            filter: ReportFilter::new(&rig.filters),
+            interpolator: Interpolator::new(rig.interpolation),
+            loss: TrackingLoss::new(rig.tracking_lost.clone(), now),
+        }
+    }
+
+    /// pick up the settings of a reloaded rig, without forgetting the pose we're in
+    pub fn set_rig(&mut self, rig: &Rig) {
+        self.filter = ReportFilter::new(&rig.filters);
+        self.interpolator.set_config(rig.interpolation);
+        self.loss.set_config(rig.tracking_lost.clone());
+    }
+
+    /// take in the latest report from the tracker. reports without a face have nothing to pose
+    /// the puppet with, so they only let the face go on being lost
+    pub fn push(&mut self, stamped: StampedReport) {
+        if stamped.report.face_found() {
+            self.loss.found(stamped.received);
+            self.interpolator.push(self.filter.apply(stamped));
+        }
+    }
+
+    /// the pose to draw at `now`. the tracker runs at its own pace, so between reports this is
+    /// whatever pose we'd expect
+    pub fn report_at(&mut self, now: Instant) -> TrackingReport {
+        self.loss.report_at(now, self.interpolator.report_at(now))
+    }
+}
@@ -1,18 +1,23 @@
 use super::camera::ScaledOrbitControl;
 use super::config::{LayerConfig, Rule};
+use super::conv::premultiplied;
+use super::pipeline::ReportPipeline;
 use super::rig::{Rig, RigLayer};
-use crate::tracker::{ControlMessage, TrackingReport};
+use super::watch::RigWatcher;
+use crate::tracker::{ControlMessage, Tracker, TrackerState, TrackingReport};
 use core::ops::Mul;
-use log::info;
-use std::sync::mpsc::{Receiver, Sender};
+use log::{error, info, warn};
 use std::sync::Arc;
+use std::time::Instant;
 use three_d::{
-    degrees, vec3, Blend, Camera, ClearState, ColorMaterial, Context, CpuMesh, Event, FrameInput,
-    FrameOutput, Gm, Key, Mat4, Mesh, RenderStates, Texture2D, Vec3, Viewport,
+    degrees, vec3, Camera, ClearState, Color, ColorMaterial, Context, CpuMesh, Event, FrameInput,
+    FrameOutput, Gm, Key, Mat4, Mesh, RenderStates, RenderTarget, Texture2D, Vec3, Viewport,
 };
 
 struct RenderLayer {
     model: Gm<Mesh, ColorMaterial>,
+    visible: bool,
+    shown_when_lost: bool,
     base_transformation: Mat4,
     configs: Vec<LayerConfig>,
 }
@@ -45,19 +50,40 @@ impl RenderLayer {
             }
         }
 
+        let material = ColorMaterial {
+            color: Color::WHITE,
+            texture: None,
+            is_transparent: true,
+            render_states: RenderStates {
+                blend: rig_layer.composite_op.blend(),
+                ..Default::default()
+            },
+        };
+
         RenderLayer {
             base_transformation: translation.mul(scale.mul(Mat4::from_translation(offset))),
             configs: rig_layer.configs.clone(),
+            visible: rig_layer.visible,
+            shown_when_lost: rig_layer.shown_when_lost,
             model: Gm::new(
                 Mesh::new(context, &CpuMesh::square()),
-                ColorMaterial {
-                    texture: Some(Arc::new(Texture2D::new(context, &rig_layer.texture))),
-                    is_transparent: true,
-                    render_states: RenderStates {
-                        blend: Blend::TRANSPARENCY,
-                        ..Default::default()
-                    },
-                    ..Default::default()
+                if rig_layer.composite_op.premultiplied() {
+                    ColorMaterial {
+                        texture: Some(Arc::new(Texture2D::new(
+                            context,
+                            &premultiplied(&rig_layer.texture, rig_layer.opacity),
+                        ))),
+                        ..material
+                    }
+                } else {
+                    ColorMaterial {
+                        color: Color {
+                            a: (rig_layer.opacity * 255.0).round() as u8,
+                            ..Color::WHITE
+                        },
+                        texture: Some(Arc::new(Texture2D::new(context, &rig_layer.texture))),
+                        ..material
+                    }
                 },
             ),
         }
@@ -74,14 +100,18 @@ impl RenderLayer {
     }
 
     fn currently_visible(&self, report: &TrackingReport) -> bool {
+        if !self.visible {
+            return false;
+        }
+
         for config in &self.configs {
             if !config.visible {
                 return false;
-            } else if let Some(rule) = config.invisible_when {
+            } else if let Some(rule) = &config.invisible_when {
                 if rule.apply(report) {
                     return false;
                 }
-            } else if let Some(rule) = config.visible_when {
+            } else if let Some(rule) = &config.visible_when {
                 if !rule.apply(report) {
                     return false;
                 }
@@ -99,14 +129,14 @@ impl RenderLayer {
     }
 }
 
-fn handle_input(frame_input: &FrameInput, control_tx: &Sender<ControlMessage>) {
+fn handle_input(frame_input: &FrameInput, tracker: &Tracker) {
     for event in &frame_input.events {
         match event {
             Event::KeyPress {
                 kind: Key::B,
                 modifiers: _,
                 handled: _,
-            } => control_tx.send(ControlMessage::Calibrate).unwrap(),
+            } => tracker.send(ControlMessage::Calibrate),
             Event::KeyPress {
                 kind: Key::C,
                 modifiers: _,
@@ -122,53 +152,178 @@ fn handle_input(frame_input: &FrameInput, control_tx: &Sender<ControlMessage>) {
     }
 }
 
-pub fn render(
-    context: Context,
-    report_rx: Receiver<TrackingReport>,
-    control_tx: Sender<ControlMessage>,
-    rig: Rig,
-) -> Box<dyn FnMut(FrameInput) -> FrameOutput> {
-    let mut camera = Camera::new_perspective(
-        Viewport {
-            x: 0,
-            y: 0,
-            width: 100,
-            height: 100,
-        },
-        vec3(0.0, 0.0, 2.0),
-        vec3(0.0, 0.0, 0.0),
-        vec3(0.0, 1.0, 0.0),
-        degrees(45.0),
-        0.1,
-        10.0,
-    );
-
-    let mut orbit_control = ScaledOrbitControl::new(*camera.target(), 1.0, 3.0, 0.02);
-
-    let mut render_layers: Vec<RenderLayer> = RenderLayer::from_rig(&rig, &context);
+/// how big the square in the corner that says the tracker isn't working is, and how far it is from
+/// the edges, in pixels
+const INDICATOR_SIZE: f32 = 16.0;
+const INDICATOR_MARGIN: f32 = 8.0;
 
-    Box::new(move |frame_input: FrameInput| {
-        camera.set_viewport(frame_input.viewport);
+/// what colour the corner square should be in `state`, if it should be there at all
+fn indicator_color(state: TrackerState) -> Option<Color> {
+    match state {
+        TrackerState::Restarting => Some(Color::new_opaque(255, 191, 0)),
+        TrackerState::Failed => Some(Color::new_opaque(255, 0, 0)),
+        TrackerState::Starting | TrackerState::Running | TrackerState::Stopped => None,
+    }
+}
 
-        orbit_control.handle_events(&mut camera, &frame_input.events);
-        handle_input(&frame_input, &control_tx);
+/// A rig's layers, uploaded to a graphics context and ready to be drawn
+pub struct Puppet {
+    camera: Camera,
+    orbit_control: ScaledOrbitControl,
+    render_layers: Vec<RenderLayer>,
 
-        let report = report_rx.recv().unwrap();
-        dbg!(&report); // XXX remove
-        let target = frame_input.screen();
+    /// a square for the corner, for when the tracker isn't working, so that the puppet standing
+    /// still doesn't look like the face standing still
+    indicator: Gm<Mesh, ColorMaterial>,
+}
+
+impl Puppet {
+    pub fn new(context: &Context, rig: &Rig) -> Puppet {
+        let camera = Camera::new_perspective(
+            Viewport {
+                x: 0,
+                y: 0,
+                width: 100,
+                height: 100,
+            },
+            vec3(0.0, 0.0, 2.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            degrees(45.0),
+            0.1,
+            10.0,
+        );
+
+        Puppet {
+            orbit_control: ScaledOrbitControl::new(*camera.target(), 1.0, 3.0, 0.02),
+            camera,
+            render_layers: RenderLayer::from_rig(rig, context),
+            indicator: Gm::new(
+                Mesh::new(context, &CpuMesh::square()),
+                ColorMaterial {
+                    color: Color::WHITE,
+                    texture: None,
+                    is_transparent: false,
+                    render_states: RenderStates::default(),
+                },
+            ),
+        }
+    }
+
+    /// swap in a different rig, keeping the camera where it is
+    pub fn set_rig(&mut self, context: &Context, rig: &Rig) {
+        self.render_layers = RenderLayer::from_rig(rig, context);
+    }
+
+    pub fn handle_events(&mut self, events: &[Event]) {
+        self.orbit_control.handle_events(&mut self.camera, events);
+    }
+
+    /// draw the puppet, posed according to `report`, into `viewport` of `target`, with a square in
+    /// the top right corner if `tracker` says the tracker isn't working
+    pub fn render(
+        &mut self,
+        target: &RenderTarget,
+        viewport: Viewport,
+        report: &TrackingReport,
+        tracker: TrackerState,
+    ) {
+        self.camera.set_viewport(viewport);
 
         target.clear(ClearState::color_and_depth(0.0, 1.0, 0.0, 1.0, 1.0));
 
-        for render_layer in &mut render_layers {
-            if !render_layer.currently_visible(&report) {
+        // rigs with layers for when tracking is lost draw only those while it is, and only the
+        // others the rest of the time
+        let lost = !report.face_found() && self.render_layers.iter().any(|l| l.shown_when_lost);
+
+        for render_layer in &mut self.render_layers {
+            if render_layer.shown_when_lost != lost || !render_layer.currently_visible(report) {
                 continue;
             }
 
-            render_layer.apply_transformation(&report);
-            target.render(&camera, &[&render_layer.model], &[]);
+            render_layer.apply_transformation(report);
+            target.render(&self.camera, &[&render_layer.model], &[]);
             target.clear(ClearState::depth(1.0));
         }
 
+        if let Some(color) = indicator_color(tracker) {
+            self.render_indicator(target, viewport, color);
+        }
+    }
+
+    fn render_indicator(&mut self, target: &RenderTarget, viewport: Viewport, color: Color) {
+        // measured in pixels from the middle of the viewport
+        let camera = Camera::new_orthographic(
+            viewport,
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            viewport.height as f32,
+            0.1,
+            10.0,
+        );
+
+        let from_middle = INDICATOR_MARGIN + INDICATOR_SIZE / 2.0;
+        self.indicator.set_transformation(
+            Mat4::from_translation(vec3(
+                viewport.width as f32 / 2.0 - from_middle,
+                viewport.height as f32 / 2.0 - from_middle,
+                0.0,
+            ))
+            .mul(Mat4::from_scale(INDICATOR_SIZE / 2.0)),
+        );
+        self.indicator.material.color = color;
+        target.render(&camera, &[&self.indicator], &[]);
+    }
+}
+
+pub fn render(
+    context: Context,
+    tracker: Tracker,
+    rig: Rig,
+) -> Box<dyn FnMut(FrameInput) -> FrameOutput> {
+    let mut puppet = Puppet::new(&context, &rig);
+    let mut watcher = RigWatcher::new(&rig.path);
+    let mut pipeline = ReportPipeline::new(&rig, Instant::now());
+    let mut tracker_state = tracker.state();
+
+    Box::new(move |frame_input: FrameInput| {
+        match watcher.poll() {
+            Some(Ok(new_rig)) => {
+                info!("reloaded {}", new_rig.path.display());
+                puppet.set_rig(&context, &new_rig);
+                pipeline.set_rig(&new_rig);
+            }
+            Some(Err(e)) => error!("could not reload rig, keeping the old one: {}", e),
+            None => (),
+        }
+
+        puppet.handle_events(&frame_input.events);
+        handle_input(&frame_input, &tracker);
+
+        let state = tracker.state();
+        if state != tracker_state {
+            match state {
+                TrackerState::Running => info!("tracking"),
+                TrackerState::Restarting => warn!("lost the tracker; holding the last pose"),
+                TrackerState::Failed => error!("the tracker could not be restarted"),
+                TrackerState::Starting | TrackerState::Stopped => (),
+            }
+            tracker_state = state;
+        }
+
+        if let Some(latest) = tracker.reports.latest() {
+            pipeline.push(latest);
+        }
+        let report = pipeline.report_at(Instant::now());
+
+        puppet.render(
+            &frame_input.screen(),
+            frame_input.viewport,
+            &report,
+            tracker_state,
+        );
+
         FrameOutput::default()
     })
 }
@@ -1,14 +1,17 @@
+use super::composite::CompositeOp;
 use super::config;
 use super::conv::from_asset;
+use super::error::RigError;
 use super::ora;
 use log::info;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use three_d::CpuTexture;
 use three_d_asset::io::RawAssets;
 use zip::read::ZipArchive;
+use zip::result::ZipError;
 
 #[derive(Debug)]
 pub struct RigLayer {
@@ -17,26 +20,51 @@ pub struct RigLayer {
     pub y: i32,
     pub name: String,
 
+    /// how opaque the layer is, from 0 to 1, including the opacity of the stacks it's in
+    pub opacity: f32,
+
+    /// whether the layer (and every stack it's in) is visible in the .ora file. hidden layers are
+    /// never drawn, whatever the config says
+    pub visible: bool,
+
+    pub composite_op: CompositeOp,
+
     /// layer configurations, in the order they should be applied (starting from the root of the
     /// stack)
     pub configs: Vec<config::LayerConfig>,
+
+    /// whether the layer (or a stack it's in) is drawn instead of the rest of the puppet when
+    /// tracking is lost
+    pub shown_when_lost: bool,
 }
 
 #[derive(Debug)]
 pub struct Rig {
+    /// the .ora file this was loaded from
+    pub path: PathBuf,
     pub width: u32,
     pub height: u32,
     pub layers: Vec<RigLayer>,
+    pub interpolation: config::InterpolationConfig,
+    pub filters: HashMap<String, config::FilterConfig>,
+    pub tracking_lost: config::TrackingLostConfig,
 }
 
 impl Rig {
-    pub fn open(ora_path: &Path) -> io::Result<Rig> {
+    pub fn open(ora_path: &Path) -> Result<Rig, RigError> {
         let mut ora = ZipArchive::new(File::open(ora_path)?)?;
         let mut layers = Vec::new();
         let mut assets = RawAssets::new();
 
         let config = config::load(ora_path)?;
         let (width, height, ora_layers) = ora::read(&mut ora)?;
+
+        config.check_layer_names(
+            ora_layers
+                .iter()
+                .flat_map(|l| l.parent_names.iter().chain([&l.name]))
+                .map(String::as_str),
+        )?;
         let layer_count = ora_layers.len();
 
         for (i, ora_layer) in ora_layers.into_iter().enumerate() {
@@ -48,36 +76,50 @@ impl Rig {
             );
 
             let mut buf = Vec::new();
-            ora.by_name(&ora_layer.src)?.read_to_end(&mut buf)?;
+            match ora.by_name(&ora_layer.src) {
+                Ok(mut f) => f.read_to_end(&mut buf)?,
+                Err(ZipError::FileNotFound) => return Err(RigError::MissingLayer(ora_layer.src)),
+                Err(e) => return Err(e.into()),
+            };
             assets.insert(&ora_layer.src, buf);
 
+            let texture = assets
+                .deserialize(&ora_layer.src)
+                .map_err(|e| RigError::UndecodableTexture(ora_layer.src.clone(), e))?;
+
+            let composite_op = CompositeOp::for_layer(&ora_layer.name, &ora_layer.composite_op);
+
             let mut configs = Vec::new();
+            let mut shown_when_lost = false;
 
             for name in [ora_layer.parent_names, vec![ora_layer.name.to_string()]].concat() {
                 if let Some(config) = config.layers.get(&name) {
-                    configs.push(*config)
+                    configs.push(config.clone())
                 }
+                shown_when_lost |= config.tracking_lost.show.contains(&name);
             }
 
             layers.push(RigLayer {
                 x: ora_layer.x,
                 y: ora_layer.y,
                 configs,
+                shown_when_lost,
                 name: ora_layer.name,
-                texture: from_asset(
-                    assets
-                        .deserialize(&ora_layer.src)
-                        .expect("this expect should be a question mark"),
-                ),
+                opacity: ora_layer.opacity.clamp(0.0, 1.0),
+                visible: ora_layer.visibility == ora::Visibility::Visible,
+                composite_op,
+                texture: from_asset(texture),
             });
         }
 
-        // XXX print out any unused config keys, since they're probably misconfigs
-
         Ok(Rig {
+            path: ora_path.to_path_buf(),
             width,
             height,
             layers,
+            interpolation: config.interpolation,
+            filters: config.filters,
+            tracking_lost: config.tracking_lost,
         })
     }
 }
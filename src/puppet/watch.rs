@@ -0,0 +1,218 @@
+//! noticing when a rig has been edited, so that it can be reloaded without a restart
+
+use super::config;
+use super::error::RigError;
+use super::rig::Rig;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// how often to look at the files on disk; the render loop runs much more often than this
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a rig's .ora file and config, and reloads the rig in the background when they change
+pub struct RigWatcher {
+    ora_path: PathBuf,
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    last_checked: Instant,
+    loading: Option<Receiver<Result<Rig, RigError>>>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl RigWatcher {
+    pub fn new(ora_path: &Path) -> RigWatcher {
+        let mut paths = vec![ora_path.to_path_buf()];
+        if let Ok(config_path) = config::path_for(ora_path) {
+            paths.push(config_path);
+        }
+
+        RigWatcher {
+            ora_path: ora_path.to_path_buf(),
+            modified: paths.iter().map(|p| modified(p)).collect(),
+            paths,
+            last_checked: Instant::now(),
+            loading: None,
+        }
+    }
+
+    fn changed(&mut self) -> bool {
+        if self.last_checked.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.last_checked = Instant::now();
+
+        let modified: Vec<Option<SystemTime>> = self.paths.iter().map(|p| modified(p)).collect();
+        if modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+        true
+    }
+
+    /// call this every frame. once the rig has been edited and reloaded, this returns the result
+    /// of loading it
+    pub fn poll(&mut self) -> Option<Result<Rig, RigError>> {
+        if let Some(loading) = &self.loading {
+            return match loading.try_recv() {
+                Ok(result) => {
+                    self.loading = None;
+                    Some(result)
+                }
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => {
+                    self.loading = None;
+                    None
+                }
+            };
+        }
+
+        if self.changed() {
+            let (tx, rx) = channel();
+            let ora_path = self.ora_path.clone();
+            thread::spawn(move || {
+                // if nobody's waiting for the rig any more, there's nothing to do with it
+                let _ = tx.send(Rig::open(&ora_path));
+            });
+            self.loading = Some(rx);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::{env, process};
+
+    /// A copy of the stick figure in a directory of its own, removed again when dropped
+    struct Copy {
+        dir: PathBuf,
+        ora_path: PathBuf,
+        config_path: PathBuf,
+
+        /// how many times we've edited the files, so that each edit can be dated later than the last
+        edits: u64,
+    }
+
+    impl Copy {
+        fn new(name: &str) -> Copy {
+            let dir = env::temp_dir().join(format!("layertuber-watch-{}-{}", name, process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let puppets = Path::new(env!("CARGO_MANIFEST_DIR")).join("puppets");
+            let ora_path = dir.join("puppet.ora");
+            fs::copy(puppets.join("stick figure/stick figure.ora"), &ora_path).unwrap();
+            let config_path = dir.join("puppet.ora.layertuber.yaml");
+            fs::write(&config_path, "layers: {}\n").unwrap();
+
+            Copy {
+                dir,
+                ora_path,
+                config_path,
+                edits: 0,
+            }
+        }
+
+        /// write `contents` to `path`, dated later than any edit before, however coarse the
+        /// filesystem's timestamps are
+        fn edit(&mut self, path: &Path, contents: &[u8]) {
+            fs::write(path, contents).unwrap();
+            self.edits += 1;
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(SystemTime::now() + Duration::from_secs(self.edits))
+                .unwrap();
+        }
+
+        fn edit_config(&mut self, config: &str) {
+            let path = self.config_path.clone();
+            self.edit(&path, config.as_bytes());
+        }
+    }
+
+    impl Drop for Copy {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// poll until the watcher has loaded the rig again
+    fn reloaded(watcher: &mut RigWatcher) -> Result<Rig, RigError> {
+        let until = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < until {
+            if let Some(result) = watcher.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the rig was never reloaded");
+    }
+
+    /// poll for long enough that the watcher would have noticed any change
+    fn quiet(watcher: &mut RigWatcher) -> bool {
+        let until = Instant::now() + CHECK_INTERVAL * 2;
+        while Instant::now() < until {
+            if watcher.poll().is_some() {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    #[test]
+    fn editing_the_config_reloads_the_rig() {
+        let mut copy = Copy::new("config");
+        let mut watcher = RigWatcher::new(&copy.ora_path);
+        assert!(quiet(&mut watcher));
+
+        copy.edit_config("tracking_lost: {after: 2}\nlayers: {}\n");
+        let rig = reloaded(&mut watcher).unwrap();
+        assert_eq!(rig.tracking_lost.after, 2.0);
+        assert!(quiet(&mut watcher));
+    }
+
+    #[test]
+    fn editing_the_ora_reloads_the_rig() {
+        let mut copy = Copy::new("ora");
+        let mut watcher = RigWatcher::new(&copy.ora_path);
+
+        let ora = fs::read(&copy.ora_path).unwrap();
+        let path = copy.ora_path.clone();
+        copy.edit(&path, &ora);
+        let rig = reloaded(&mut watcher).unwrap();
+        assert_eq!(rig.path, copy.ora_path);
+        assert!(!rig.layers.is_empty());
+    }
+
+    #[test]
+    fn broken_edits_are_reported_and_fixed_ones_picked_up() {
+        let mut copy = Copy::new("broken");
+        let mut watcher = RigWatcher::new(&copy.ora_path);
+
+        // the renderer keeps drawing the rig it had when loading fails
+        copy.edit_config("layers: [\n");
+        assert!(matches!(reloaded(&mut watcher), Err(RigError::Config(_))));
+        assert!(quiet(&mut watcher));
+
+        // and gets the fixed one once there is one
+        copy.edit_config("tracking_lost: {after: 3}\nlayers: {}\n");
+        let rig = reloaded(&mut watcher).unwrap();
+        assert_eq!(rig.tracking_lost.after, 3.0);
+
+        let path = copy.ora_path.clone();
+        copy.edit(&path, b"not a zip file");
+        assert!(reloaded(&mut watcher).is_err());
+    }
+}
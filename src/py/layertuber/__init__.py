@@ -20,23 +20,47 @@ def _parse_args() -> argparse.Namespace:
     parser.add_argument('--show-features', action='store_true', help=(
         'Show an additional window with your webcam feed and facial feature detection spots overlaid on it.'
     ))
+    parser.add_argument('--width', type=int, default=800, help='The width, in pixels, to ask the camera for.')
+    parser.add_argument('--height', type=int, default=600, help='The height, in pixels, to ask the camera for.')
+    parser.add_argument('--fps', type=int, default=30, help='How many frames per second to ask the camera for.')
+    parser.add_argument('--model', type=int, default=3, choices=range(-3, 5), help=(
+        'Which OpenSeeFace model to track with, from -3 (fastest) to 4 (best at winks).'
+    ))
+    parser.add_argument('--max-faces', type=int, default=1, help=(
+        'How many faces to look for. Only the first one found is reported.'
+    ))
     return parser.parse_args()
 
 
 def main() -> None:
-    from layertuber.tracking.face import FaceTracker
+    from layertuber.commands import Commands
     from layertuber.reporter import Reporter
 
     args = _parse_args()
 
+    # say hello before doing anything slow, so that an incompatible renderer finds out quickly
     reporter = Reporter()
-    tracker = FaceTracker(capture=args.camera, show_features=args.show_features)
+    reporter.hello()
+
+    from layertuber.tracking.face import FaceTracker
+
+    commands = Commands()
+    tracker = FaceTracker(
+        capture=args.camera,
+        show_features=args.show_features,
+        width=args.width,
+        height=args.height,
+        fps=args.fps,
+        model=args.model,
+        max_faces=args.max_faces,
+    )
     for report in tracker.begin_loop():
         reporter.report(report)
 
-        message = input()
-        if message == 'calibrate':
-            tracker.calibrate()
+        for command in commands.pending():
+            if command is None:
+                # the renderer has gone away
+                return
 
 
 if __name__ == '__main__':
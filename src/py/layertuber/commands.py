@@ -0,0 +1,30 @@
+from queue import Empty, Queue
+from sys import stdin
+from threading import Thread
+from typing import Iterator, Optional
+
+
+class Commands:
+    """
+    Reads commands from the renderer on stdin without holding up tracking. A
+    command of None means stdin has closed.
+    """
+
+    queue: 'Queue[Optional[str]]'
+
+    def __init__(self) -> None:
+        self.queue = Queue()
+        Thread(target=self._read, daemon=True).start()
+
+    def _read(self) -> None:
+        for line in stdin:
+            self.queue.put(line.strip())
+
+        self.queue.put(None)
+
+    def pending(self) -> Iterator[Optional[str]]:
+        while True:
+            try:
+                yield self.queue.get_nowait()
+            except Empty:
+                return
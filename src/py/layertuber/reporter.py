@@ -1,16 +1,29 @@
 from dataclasses import dataclass
 from sys import stdout
-from typing import Literal, Optional
+from typing import Literal, Optional, get_args
 
 import orjson
 
 from scipy.spatial.transform import Rotation
 
-from .tracking.report import TrackingReport
+from .tracking.report import (
+    FloatFromTrackingReport, RotationFromTrackingReport, TrackingReport, Vec2FromTrackingReport,
+)
 
 
+# the version of the conversation we have with the renderer over stdin and stdout. this must match
+# PROTOCOL_VERSION in tracker/face.rs
+PROTOCOL_VERSION = 4
+
 REPORT_TYPES: tuple[Literal['floats', 'rotations', 'vec2s'], ...] = ('floats', 'rotations', 'vec2s')
 
+# the kind of every field we report, as named in FieldKind in tracker/report.rs
+FIELDS: dict[str, str] = {
+    **{name: 'float' for name in get_args(FloatFromTrackingReport)},
+    **{name: 'rotation' for name in get_args(RotationFromTrackingReport)},
+    **{name: 'vec2' for name in get_args(Vec2FromTrackingReport)},
+}
+
 
 def default(o: object) -> object:
     if isinstance(o, Rotation):
@@ -21,6 +34,11 @@ def default(o: object) -> object:
 
 @dataclass
 class Reporter:
+    def hello(self) -> None:
+        stdout.buffer.write(orjson.dumps({'hello': {'protocol': PROTOCOL_VERSION, 'fields': FIELDS}}))
+        stdout.buffer.write(b'\n')
+        stdout.buffer.flush()
+
     def report(self, report: Optional[TrackingReport]) -> None:
         if report is not None:
             flattened_report = {
@@ -30,3 +48,4 @@ class Reporter:
             stdout.buffer.write(orjson.dumps(flattened_report, default=default, option=orjson.OPT_SERIALIZE_NUMPY))
 
         stdout.buffer.write(b'\n')
+        stdout.buffer.flush()
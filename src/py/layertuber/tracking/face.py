@@ -6,7 +6,7 @@ import cv2
 from scipy.spatial.transform import Rotation
 
 from .report import TrackingReport
-from .utils import average_of_2d_vectors, flip, px_to_center_offset_2d, subtract
+from .utils import average_of_2d_vectors, flip, px_to_center_offset_2d
 from ..utils.cv import PINK, draw_dot_on_frame
 from ..vendor.OpenSeeFace.input_reader import InputReader
 from ..vendor.OpenSeeFace.tracker import FaceInfo, Tracker
@@ -14,9 +14,6 @@ from ..vendor.OpenSeeFace.tracker import FaceInfo, Tracker
 
 logger = logging.getLogger('face tracking')
 
-REQUEST_INPUT_WIDTH = 800
-REQUEST_INPUT_HEIGHT = 600
-
 TrackerControlEvent = Literal['calibrate', 'next_frame']
 CALIBRATE: TrackerControlEvent = 'calibrate'
 NEXT_FRAME: TrackerControlEvent = 'next_frame'
@@ -28,16 +25,18 @@ class FaceTracker:
     height: int
     width: int
     show_features: bool
-    neutral_report: TrackingReport
 
     def __init__(
         self,
         capture: int = 0,
         show_features: bool = False,
+        width: int = 800,
+        height: int = 600,
+        fps: int = 30,
+        model: int = 3,
+        max_faces: int = 1,
     ) -> None:
-        self.reader = InputReader(
-            capture=capture, raw_rgb=False, width=REQUEST_INPUT_WIDTH, height=REQUEST_INPUT_HEIGHT, fps=30
-        )
+        self.reader = InputReader(capture=capture, raw_rgb=False, width=width, height=height, fps=fps)
         self.show_features = show_features
 
         ret, frame = self.reader.read()
@@ -46,15 +45,7 @@ class FaceTracker:
         self.height, self.width, channels = frame.shape
         logger.debug(f'w: {self.width}, h: {self.height}, c: {channels}')
 
-        self.tracker = Tracker(self.width, self.height, silent=True)
-        self.calibrate()
-
-    def calibrate(self) -> None:
-        while True:
-            report = self.get_raw_report()
-            if report is not None:
-                self.neutral_report = report
-                break
+        self.tracker = Tracker(self.width, self.height, model_type=model, max_faces=max_faces, silent=True)
 
     def get_face(self) -> Optional[FaceInfo]:
         ret, input_frame = self.reader.read()
@@ -67,23 +58,25 @@ class FaceTracker:
         if not faces:
             return None
 
-        face, = faces  # we only allow one
+        # there may be more than one, if we were asked to look for more, but only one can be the puppet
+        face = faces[0]
 
         if self.show_features:
             # `lms` here means `landmarks`
-            for _part_number, (feature_x, feature_y, _c) in enumerate(face.lms):
-                draw_dot_on_frame(input_frame, PINK, 2, feature_x, feature_y)
+            for f in faces:
+                for _part_number, (feature_x, feature_y, _c) in enumerate(f.lms):
+                    draw_dot_on_frame(input_frame, PINK, 2, feature_x, feature_y)
 
             cv2.imshow('first', input_frame)
             cv2.waitKey(1)
 
         return face
 
-    def get_raw_report(self) -> Optional[TrackingReport]:
+    def get_report(self) -> TrackingReport:
         face = self.get_face()
 
         if face is None:
-            return None
+            return dict(floats=dict(face_found=0), rotations=dict(), vec2s=dict())
 
         size = (self.width, self.height)
         eye_blink = face.eye_blink or (1, 1)
@@ -118,6 +111,8 @@ class FaceTracker:
 
                 mouth_open=features.get('mouth_open', 0),
                 mouth_wide=features.get('mouth_wide', 0),
+
+                face_found=1,
             ),
             rotations=dict(
                 head_rotation=Rotation.from_quat(face.quaternion),
@@ -130,19 +125,6 @@ class FaceTracker:
             ),
         )
 
-    def get_report(self) -> Optional[TrackingReport]:
-        report = self.get_raw_report()
-        if report is None:
-            return report
-
-        for vk, vv in report['vec2s'].items():
-            report['vec2s'][vk] = subtract(vv, self.neutral_report['vec2s'][vk])
-
-        for rk, rv in report['rotations'].items():
-            report['rotations'][rk] = (rv * self.neutral_report['rotations'][rk].inv())
-
-        return report
-
-    def begin_loop(self) -> Iterator[Optional[TrackingReport]]:
+    def begin_loop(self) -> Iterator[TrackingReport]:
         while self.reader.is_open():
             yield self.get_report()
@@ -1,5 +1,7 @@
 """
-The types here must reflect the types defined in tracker/report.rs
+The renderer is told about the fields here when the tracker starts, and checks them against
+STANDARD_CHANNELS in tracker/report.rs. Rigs can also use channels that aren't listed here, as long
+as they declare them
 """
 
 from typing import Literal, TypedDict
@@ -13,6 +15,7 @@ FloatFromTrackingReport = Literal[
     'eyebrow_steepness_left', 'eyebrow_steepness_right', 'eyebrow_steepness',
     'eyebrow_updown_left', 'eyebrow_updown_right', 'eyebrow_updown',
     'mouth_open', 'mouth_wide',
+    'face_found',
 ]
 RotationFromTrackingReport = Literal['head_rotation']
 Vec2FromTrackingReport = Literal['face_position', 'left_gaze', 'right_gaze', 'gaze']
@@ -1,2 +1,219 @@
+//! the bundled tracker, built by build.rs and extracted to disk so that it can be run. only
+//! release builds bundle it, but the extracting is the same for any bytes, so it's tested in every
+//! build
+
+#[cfg(not(debug_assertions))]
+use dirs::cache_dir;
+use log::{info, warn};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 #[cfg(not(debug_assertions))]
 pub const TRACKER_BIN: &[u8] = include_bytes!("../py/dist/layertuber");
+
+/// what every extracted tracker's file name starts with, followed by the hash of its contents
+const PREFIX: &str = "layertuber-tracker-";
+
+/// FNV-1a, which is plenty to tell one build of the tracker from another
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// put the tracker somewhere it can be run from, returning where
+#[cfg(not(debug_assertions))]
+pub fn extract() -> io::Result<PathBuf> {
+    let dir = cache_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "there is no cache directory to put the tracker in",
+        )
+    })?;
+    extract_to(&dir, TRACKER_BIN)
+}
+
+/// write `tracker` to `dir`, returning where. the file name depends on the tracker's contents, so
+/// every instance of the same version of layertuber can share one copy without getting in each
+/// other's way, and it's left there for next time. copies of other versions are removed
+fn extract_to(dir: &Path, tracker: &[u8]) -> io::Result<PathBuf> {
+    let name = format!("{}{:016x}", PREFIX, hash(tracker));
+    let path = dir.join(&name);
+
+    // the name already says what's in it, so the length is enough to catch it having been cut
+    // short, without reading the whole thing back on every launch
+    match fs::metadata(&path) {
+        Ok(m) if m.is_file() && m.len() == tracker.len() as u64 => return Ok(path),
+        Ok(_) => warn!(
+            "{} is not the tracker it should be; replacing it",
+            path.display()
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+
+    // write somewhere nobody else is writing to, then move it into place all at once, so that
+    // nobody ever runs half a tracker. instances in the same process need telling apart too
+    static EXTRACTIONS: AtomicUsize = AtomicUsize::new(0);
+    let temporary = path.with_extension(format!(
+        "{}-{}.tmp",
+        process::id(),
+        EXTRACTIONS.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o700)
+        .open(&temporary)
+        .and_then(|mut file| {
+            file.write_all(tracker)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temporary, &path));
+
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temporary);
+        return Err(e);
+    }
+
+    info!("extracted tracker to {}", path.display());
+    prune(dir, &name);
+    Ok(path)
+}
+
+/// remove trackers extracted by other versions of layertuber from `dir`, keeping the one called
+/// `keep`. half-written ones are left alone, since they may still be being written
+fn prune(dir: &Path, keep: &str) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(
+                "could not look for old trackers in {}: {}",
+                dir.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let old = name != keep
+            && name.strip_prefix(PREFIX).is_some_and(|hash| {
+                hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+            });
+        if !old {
+            continue;
+        }
+
+        match fs::remove_file(entry.path()) {
+            Ok(()) => info!("removed old tracker {}", entry.path().display()),
+            Err(e) => warn!(
+                "could not remove old tracker {}: {}",
+                entry.path().display(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, SystemTime};
+
+    /// an empty directory of its own for each test, removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = env::temp_dir().join(format!("layertuber-bin-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn modified(path: &Path) -> SystemTime {
+        fs::metadata(path).unwrap().modified().unwrap()
+    }
+
+    #[test]
+    fn trackers_are_extracted_runnable_and_named_for_their_contents() {
+        let dir = TempDir::new("extract");
+
+        let path = extract_to(&dir.0, b"tracker, version 1").unwrap();
+        assert_eq!(path.parent(), Some(dir.0.as_path()));
+        assert_eq!(fs::read(&path).unwrap(), b"tracker, version 1");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        let other = extract_to(&dir.0, b"tracker, version 2").unwrap();
+        assert_ne!(path, other);
+    }
+
+    #[test]
+    fn extracted_trackers_are_reused() {
+        let dir = TempDir::new("reuse");
+        let path = extract_to(&dir.0, b"tracker").unwrap();
+
+        let long_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+
+        assert_eq!(extract_to(&dir.0, b"tracker").unwrap(), path);
+        assert_eq!(modified(&path), long_ago);
+    }
+
+    #[test]
+    fn trackers_that_were_cut_short_are_replaced() {
+        let dir = TempDir::new("short");
+        let path = extract_to(&dir.0, b"tracker").unwrap();
+        fs::write(&path, b"trac").unwrap();
+
+        assert_eq!(extract_to(&dir.0, b"tracker").unwrap(), path);
+        assert_eq!(fs::read(&path).unwrap(), b"tracker");
+    }
+
+    #[test]
+    fn other_versions_are_removed() {
+        let dir = TempDir::new("prune");
+        let old = dir.0.join(format!("{}{:016x}", PREFIX, 1234));
+        let half_written = dir.0.join(format!("{}{:016x}.1-0.tmp", PREFIX, 5678));
+        let unrelated = dir.0.join(format!("{}notes", PREFIX));
+        for path in [&old, &half_written, &unrelated] {
+            fs::write(path, b"").unwrap();
+        }
+
+        let path = extract_to(&dir.0, b"tracker").unwrap();
+        assert!(path.exists());
+        assert!(!old.exists());
+        assert!(half_written.exists());
+        assert!(unrelated.exists());
+    }
+}
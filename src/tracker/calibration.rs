@@ -0,0 +1,144 @@
+//! the neutral pose that reports are made relative to, kept between runs
+
+use super::TrackingReport;
+use dirs::config_dir;
+use log::{error, info};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// where every tracker's neutral pose is kept. each tracker has its own idea of what a neutral
+/// pose looks like, so they're stored by name
+fn calibration_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("layertuber").join("calibration.json"))
+}
+
+fn read_all(path: &PathBuf) -> io::Result<BTreeMap<String, TrackingReport>> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(serde_json::from_str(&s)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Makes a tracker's reports relative to a neutral pose. Until there is one, the next report
+/// becomes it.
+pub struct Calibration {
+    /// the tracker this is for, and where to keep its neutral pose, if anywhere
+    stored_as: Option<(&'static str, PathBuf)>,
+    neutral: Option<TrackingReport>,
+}
+
+impl Calibration {
+    /// a calibration that starts afresh and is forgotten when it's dropped
+    pub fn new() -> Calibration {
+        Calibration {
+            stored_as: None,
+            neutral: None,
+        }
+    }
+
+    /// the calibration last saved for the tracker called `name`, or a fresh one if there isn't
+    /// one yet
+    pub fn load(name: &'static str) -> Calibration {
+        match calibration_path() {
+            Some(path) => Calibration::load_from(path, name),
+            None => {
+                error!("nowhere to keep calibration; it will be lost when the tracker stops");
+                Calibration::new()
+            }
+        }
+    }
+
+    /// like `load`, but keeping calibrations in the file at `path` rather than the usual place
+    pub fn load_from(path: PathBuf, name: &'static str) -> Calibration {
+        let neutral = match read_all(&path) {
+            Ok(mut all) => all.remove(name),
+            Err(e) => {
+                error!("could not read {}: {}", path.display(), e);
+                None
+            }
+        };
+
+        if neutral.is_some() {
+            info!("using the saved calibration for {}", name);
+        }
+
+        Calibration {
+            stored_as: Some((name, path)),
+            neutral,
+        }
+    }
+
+    /// make the next report the neutral one
+    pub fn reset(&mut self) {
+        self.neutral = None;
+    }
+
+    pub fn apply(&mut self, report: TrackingReport) -> TrackingReport {
+        // there's nothing to calibrate against without a face
+        if !report.face_found() {
+            return report;
+        }
+
+        if self.neutral.is_none() {
+            self.neutral = Some(report.clone());
+            self.save();
+        }
+
+        match &self.neutral {
+            Some(neutral) => report.relative_to(neutral),
+            None => report,
+        }
+    }
+
+    /// write our neutral pose alongside everyone else's. two instances saving at once both read
+    /// the file before writing it, so the last one to finish wins, and the other's change is lost
+    /// until it saves again. that's fine for something that only changes when someone asks
+    fn save(&self) {
+        let (name, path) = match &self.stored_as {
+            Some(s) => s,
+            None => return,
+        };
+
+        let saved = read_all(path).and_then(|mut all| {
+            if let Some(neutral) = &self.neutral {
+                all.insert(name.to_string(), neutral.clone());
+            }
+
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            // write somewhere nobody else is writing to first, so that a crash (or another
+            // instance saving at the same time) can't leave half a file behind
+            static SAVES: AtomicUsize = AtomicUsize::new(0);
+            let temporary = path.with_extension(format!(
+                "json.{}-{}.tmp",
+                process::id(),
+                SAVES.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            let written = fs::write(&temporary, serde_json::to_string_pretty(&all)?)
+                .and_then(|()| fs::rename(&temporary, path));
+            if written.is_err() {
+                let _ = fs::remove_file(&temporary);
+            }
+            written
+        });
+
+        match saved {
+            Ok(()) => info!("saved calibration for {} to {}", name, path.display()),
+            Err(e) => error!("could not save calibration to {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Calibration {
+        Calibration::new()
+    }
+}
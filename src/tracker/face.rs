@@ -0,0 +1,310 @@
+use super::calibration::Calibration;
+use super::report::{check_fields, FieldKind};
+use super::{
+    ControlMessage, Interrupter, RunTrackerError, TrackerBackend, TrackerOptions, TrackingReport,
+};
+use log::error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use subprocess::{ExitStatus, Popen, PopenConfig, Redirection};
+
+/// the version of the conversation we have with the tracker over its stdin and stdout. this
+/// must match `PROTOCOL_VERSION` in reporter.py
+const PROTOCOL_VERSION: u32 = 4;
+
+/// the first thing the tracker says, before any reports
+#[derive(Deserialize)]
+struct HelloMessage {
+    hello: Hello,
+}
+
+#[derive(Deserialize)]
+struct Hello {
+    protocol: u32,
+
+    /// the name and kind of every field in the reports to come
+    #[serde(default)]
+    fields: HashMap<String, FieldKind>,
+}
+
+/// The bundled OpenSeeFace tracker, run as a subprocess that reports to us on stdout
+pub struct FaceTracker {
+    options: TrackerOptions,
+    calibration: Calibration,
+    p: Option<Arc<Mutex<Popen>>>,
+
+    // taken out of `p`, so that reading doesn't stop other threads from killing the process. we
+    // don't write anything to stdin, but the tracker stops when it's closed
+    stdin: Option<File>,
+    stdout: Option<BufReader<File>>,
+
+    // shared with the interrupter, so that the process it kills doesn't look like a crash
+    stopping: Arc<AtomicBool>,
+}
+
+impl FaceTracker {
+    pub fn new(options: TrackerOptions, calibration: Calibration) -> FaceTracker {
+        FaceTracker {
+            options,
+            calibration,
+            p: None,
+            stdin: None,
+            stdout: None,
+            stopping: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    /// whether the process has gone away, logging why if it has
+    fn exited(&mut self) -> bool {
+        let status = match &self.p {
+            Some(p) => p.lock().unwrap().poll(),
+            None => return true,
+        };
+
+        match status {
+            None => false,
+            Some(_) if self.stopping() => true,
+            Some(ExitStatus::Exited(s)) => {
+                error!("tracker died with exit code {}", s);
+                true
+            }
+            Some(ExitStatus::Signaled(s)) => {
+                error!("tracker died with signal {}", s);
+                true
+            }
+            Some(e) => {
+                error!("tracker died for some reason: {:?}", e);
+                true
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+
+        match self.stdout.as_mut()?.read_line(&mut line) {
+            Ok(0) => {
+                if !self.stopping() {
+                    error!("tracker closed its output");
+                }
+                None
+            }
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                }
+                Some(line)
+            }
+            Err(e) => {
+                if !self.stopping() {
+                    error!("no tracking report: {}", e);
+                }
+                None
+            }
+        }
+    }
+
+    /// make sure the tracker speaks the same protocol as us before we try to understand anything
+    /// else it says
+    fn expect_hello(&mut self) -> Result<(), RunTrackerError> {
+        check_hello(self.read_line())
+    }
+}
+
+/// check `line`, the first thing the tracker said, if it said anything. a tracker that falls over
+/// before saying hello properly may well start next time, so that's an ordinary failure; only a
+/// hello we understand that doesn't match what we need means restarting won't help
+fn check_hello(line: Option<String>) -> Result<(), RunTrackerError> {
+    let line = line.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "tracker exited without saying hello",
+        )
+    })?;
+
+    let hello: HelloMessage = serde_json::from_str(&line).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected a hello, got {} ({})", line, e),
+        )
+    })?;
+
+    if hello.hello.protocol != PROTOCOL_VERSION {
+        return Err(RunTrackerError::Incompatible(format!(
+            "tracker speaks protocol version {}, but we need version {}",
+            hello.hello.protocol, PROTOCOL_VERSION
+        )));
+    }
+
+    check_fields(&hello.hello.fields).map_err(RunTrackerError::Incompatible)
+}
+
+/// ask `p` to exit, unless it already has
+fn terminate(p: &Mutex<Popen>) {
+    let mut p = p.lock().unwrap();
+    if p.poll().is_none() {
+        if let Err(e) = p.terminate() {
+            error!("could not stop tracker: {}", e);
+        }
+    }
+}
+
+impl TrackerBackend for FaceTracker {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        self.stopping.store(false, Ordering::SeqCst);
+        let mut p = run_tracker(&self.options)?;
+        self.stdin = p.stdin.take();
+        self.stdout = p.stdout.take().map(BufReader::new);
+        self.p = Some(Arc::new(Mutex::new(p)));
+        self.expect_hello()
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        loop {
+            if self.stopping() || self.exited() {
+                return None;
+            }
+
+            let line = self.read_line()?;
+
+            if !line.is_empty() {
+                match serde_json::from_str(&line) {
+                    Ok(r) => return Some(self.calibration.apply(r)),
+                    Err(e) => error!("got bad data from tracker: {} ({})", line, e),
+                };
+            }
+        }
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        match message {
+            ControlMessage::Calibrate => self.calibration.reset(),
+            ControlMessage::Die => {
+                self.stopping.store(true, Ordering::SeqCst);
+                if let Some(p) = &self.p {
+                    terminate(p);
+                }
+            }
+        }
+    }
+
+    fn failed(&self) -> bool {
+        // we only stop reporting without being asked to if something went wrong
+        !self.stopping()
+    }
+
+    fn interrupter(&self) -> Option<Interrupter> {
+        let p = self.p.clone()?;
+        let stopping = self.stopping.clone();
+        Some(Box::new(move || {
+            stopping.store(true, Ordering::SeqCst);
+            terminate(&p);
+        }))
+    }
+
+    fn shutdown(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        self.stdin = None;
+        self.stdout = None;
+
+        if let Some(p) = self.p.take() {
+            terminate(&p);
+            if let Err(e) = p.lock().unwrap().wait() {
+                error!("could not wait for tracker to exit: {}", e);
+            }
+        }
+    }
+}
+
+/// the command that starts the tracker, without any options. debug builds run the python
+/// directly, so that changes to it don't need a rebuild
+fn tracker_command() -> io::Result<Vec<OsString>> {
+    #[cfg(not(debug_assertions))]
+    let command = vec![super::bin::extract()?.into_os_string()];
+
+    #[cfg(debug_assertions)]
+    let command = ["python", "src/py/layertuber/__init__.py"]
+        .map(OsString::from)
+        .to_vec();
+
+    Ok(command)
+}
+
+fn run_tracker(options: &TrackerOptions) -> Result<Popen, RunTrackerError> {
+    let mut args = tracker_command()?;
+
+    let option_args = [
+        format!("--camera={}", options.camera_index),
+        format!("--width={}", options.capture_width),
+        format!("--height={}", options.capture_height),
+        format!("--fps={}", options.fps),
+        format!("--model={}", options.model),
+        format!("--max-faces={}", options.max_faces),
+    ];
+    args.extend(option_args.map(OsString::from));
+
+    if options.show_features {
+        args.push(OsString::from("--show-features"))
+    }
+
+    Ok(Popen::create(
+        &args,
+        PopenConfig {
+            stdin: Redirection::Pipe,
+            stdout: Redirection::Pipe,
+            ..Default::default()
+        },
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(protocol: u32, mouth_open: &str) -> Option<String> {
+        Some(format!(
+            r#"{{"hello": {{"protocol": {}, "fields": {{"mouth_open": "{}"}}}}}}"#,
+            protocol, mouth_open
+        ))
+    }
+
+    #[test]
+    fn matching_hellos_are_fine() {
+        assert!(check_hello(hello(PROTOCOL_VERSION, "float")).is_ok());
+    }
+
+    #[test]
+    fn no_hello_is_worth_another_try() {
+        assert!(matches!(
+            check_hello(None),
+            Err(RunTrackerError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            check_hello(Some("Traceback (most recent call last):".to_string())),
+            Err(RunTrackerError::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+
+    #[test]
+    fn mismatched_hellos_are_incompatible() {
+        assert!(matches!(
+            check_hello(hello(PROTOCOL_VERSION + 1, "float")),
+            Err(RunTrackerError::Incompatible(_))
+        ));
+        assert!(matches!(
+            check_hello(hello(PROTOCOL_VERSION, "vec2")),
+            Err(RunTrackerError::Incompatible(_))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+//! receiving ARKit face tracking from a phone running iFacialMocap, which sends it as text over UDP
+
+use super::calibration::Calibration;
+use super::report::ARKIT_BLENDSHAPES;
+use super::udp::UdpListener;
+use super::{ControlMessage, Interrupter, RunTrackerError, TrackerBackend, TrackingReport};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use three_d::{Deg, Quaternion, Rotation3};
+
+/// the port iFacialMocap listens on, and sends to
+pub const PORT: u16 = 49983;
+
+/// what we send the phone to ask it to start sending us tracking
+const HANDSHAKE: &[u8] = b"iFacialMocap_sahuasouryya9218sauhuiayeta91555dy3719";
+
+/// what we understood of one packet
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Packet {
+    /// named as ARKit names them, from 0 to 1
+    pub blendshapes: HashMap<String, f32>,
+
+    /// in degrees
+    pub head_rotation: Option<[f32; 3]>,
+
+    /// in metres
+    pub head_position: Option<[f32; 3]>,
+}
+
+/// iFacialMocap names sided blendshapes like `eyeBlink_L`, where ARKit says `eyeBlinkLeft`
+fn arkit_name(name: &str) -> String {
+    if let Some(name) = name.strip_suffix("_L") {
+        format!("{}Left", name)
+    } else if let Some(name) = name.strip_suffix("_R") {
+        format!("{}Right", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn parse_floats(values: &str) -> Result<Vec<f32>, String> {
+    values
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse()
+                .map_err(|e| format!("bad number `{}`: {}", v, e))
+        })
+        .collect()
+}
+
+impl Packet {
+    /// parse something like `mouthSmile_R-12|jawOpen-40|...|=head#-21.4,-6.0,-6.6,-0.03,-0.1,-0.65|`.
+    /// newer versions of iFacialMocap separate blendshape values with `&` rather than `-`
+    pub fn parse(packet: &[u8]) -> Result<Packet, String> {
+        let text = std::str::from_utf8(packet).map_err(|e| e.to_string())?;
+        let mut parsed = Packet::default();
+
+        for item in text.split('|').map(str::trim).filter(|i| !i.is_empty()) {
+            if let Some((name, values)) = item.split_once('#') {
+                let values = parse_floats(values)?;
+
+                if name.trim_start_matches('=') == "head" {
+                    match values[..] {
+                        [rx, ry, rz, px, py, pz] => {
+                            parsed.head_rotation = Some([rx, ry, rz]);
+                            parsed.head_position = Some([px, py, pz]);
+                        }
+                        _ => return Err(format!("expected 6 numbers for the head in `{}`", item)),
+                    }
+                }
+
+                // eye rotations are also reported, but ARKit's eyeLook blendshapes say the same
+                continue;
+            }
+
+            let (name, value) = item
+                .split_once(['&', '-'])
+                .ok_or_else(|| format!("`{}` is not a blendshape", item))?;
+            let value: f32 = value
+                .parse()
+                .map_err(|e| format!("bad value for `{}`: {}", name, e))?;
+
+            parsed.blendshapes.insert(arkit_name(name), value / 100.0);
+        }
+
+        Ok(parsed)
+    }
+
+    /// ARKit's blendshapes, along with the bundled tracker's channels made from them so that rigs
+    /// made for it still work. there's nothing to make `gaze` from, so it stays at rest
+    pub fn report(&self) -> TrackingReport {
+        let mut report = TrackingReport::default();
+        let blendshape = |name: &str| self.blendshapes.get(name).copied().unwrap_or(0.0);
+
+        report.floats.extend(self.blendshapes.clone());
+
+        // the bundled tracker's blinks are how open the eyes are, where ARKit's are how closed
+        let blink_left = 1.0 - blendshape("eyeBlinkLeft");
+        let blink_right = 1.0 - blendshape("eyeBlinkRight");
+        let eyebrow_left = blendshape("browOuterUpLeft") - blendshape("browDownLeft");
+        let eyebrow_right = blendshape("browOuterUpRight") - blendshape("browDownRight");
+
+        let standard = [
+            ("blink", (blink_left + blink_right) / 2.0),
+            ("blink_left", blink_left),
+            ("blink_right", blink_right),
+            ("eyebrow_updown", (eyebrow_left + eyebrow_right) / 2.0),
+            ("eyebrow_updown_left", eyebrow_left),
+            ("eyebrow_updown_right", eyebrow_right),
+            ("mouth_open", blendshape("jawOpen")),
+            (
+                "mouth_wide",
+                (blendshape("mouthStretchLeft") + blendshape("mouthStretchRight")) / 2.0,
+            ),
+        ];
+        for (name, value) in standard {
+            report.floats.insert(name.to_string(), value);
+        }
+
+        if let Some([rx, ry, rz]) = self.head_rotation {
+            // iFacialMocap is made for unity, which applies roll, then pitch, then yaw
+            let q = Quaternion::from_angle_y(Deg(ry))
+                * Quaternion::from_angle_x(Deg(rx))
+                * Quaternion::from_angle_z(Deg(rz));
+            report
+                .rotations
+                .insert("head_rotation".to_string(), [q.v.x, q.v.y, q.v.z, q.s]);
+        }
+
+        if let Some([px, py, _]) = self.head_position {
+            // in metres, and y is up rather than down, so rigs will want a bigger scale than they
+            // would with the bundled tracker
+            report.vec2s.insert("face_position".to_string(), [px, -py]);
+        }
+
+        report
+    }
+}
+
+/// the blendshapes ARKit has that a packet didn't mention
+fn missing_blendshapes(packet: &Packet) -> Vec<&'static str> {
+    ARKIT_BLENDSHAPES
+        .iter()
+        .copied()
+        .filter(|name| !packet.blendshapes.contains_key(*name))
+        .collect()
+}
+
+/// Asks a phone running iFacialMocap to send us its tracking, then listens for it
+pub struct IFacialMocapTracker {
+    phone: SocketAddr,
+    listener: UdpListener,
+
+    calibration: Calibration,
+
+    /// whether we've looked for missing blendshapes since starting
+    checked: bool,
+}
+
+impl IFacialMocapTracker {
+    /// `phone` is where iFacialMocap is running, and `port` is where we listen. iFacialMocap
+    /// always sends to `PORT`, so that's the one to use unless the phone is pretend
+    pub fn new(phone: SocketAddr, port: u16, calibration: Calibration) -> IFacialMocapTracker {
+        IFacialMocapTracker {
+            phone,
+            listener: UdpListener::new("iFacialMocap", port),
+            calibration,
+            checked: false,
+        }
+    }
+}
+
+impl TrackerBackend for IFacialMocapTracker {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        self.checked = false;
+        self.listener.bind()?;
+        self.listener.send_to(HANDSHAKE, self.phone)?;
+        info!("asked {} for tracking", self.phone);
+        Ok(())
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        loop {
+            match Packet::parse(self.listener.recv()?) {
+                Ok(packet) => {
+                    if !self.checked {
+                        let missing = missing_blendshapes(&packet);
+                        if !missing.is_empty() {
+                            warn!(
+                                "iFacialMocap is not sending {}; they will stay at rest",
+                                missing.join(", ")
+                            );
+                        }
+                        self.checked = true;
+                    }
+                    return Some(self.calibration.apply(packet.report()));
+                }
+                Err(e) => error!("got a bad packet from iFacialMocap: {}", e),
+            }
+        }
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        match message {
+            ControlMessage::Calibrate => self.calibration.reset(),
+            ControlMessage::Die => self.listener.stop(),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.listener.failed()
+    }
+
+    fn interrupter(&self) -> Option<Interrupter> {
+        Some(self.listener.interrupter())
+    }
+
+    fn shutdown(&mut self) {
+        self.listener.close();
+    }
+}
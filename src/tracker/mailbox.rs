@@ -0,0 +1,40 @@
+//! a channel that only holds on to the most recent value, so that a fast receiver never waits
+//! and a slow receiver never falls behind
+
+use std::sync::{Arc, Mutex};
+
+pub struct MailboxSender<T> {
+    slot: Arc<Mutex<Option<T>>>,
+}
+
+pub struct MailboxReceiver<T> {
+    slot: Arc<Mutex<Option<T>>>,
+}
+
+pub fn mailbox<T>() -> (MailboxSender<T>, MailboxReceiver<T>) {
+    let slot = Arc::new(Mutex::new(None));
+    (
+        MailboxSender { slot: slot.clone() },
+        MailboxReceiver { slot },
+    )
+}
+
+impl<T> MailboxSender<T> {
+    /// replace whatever is waiting with `value`. fails, handing the value back, if the receiver
+    /// has gone away
+    pub fn send(&self, value: T) -> Result<(), T> {
+        if Arc::strong_count(&self.slot) < 2 {
+            return Err(value);
+        }
+
+        *self.slot.lock().unwrap() = Some(value);
+        Ok(())
+    }
+}
+
+impl<T> MailboxReceiver<T> {
+    /// the most recent value, if one has arrived since we last looked
+    pub fn latest(&self) -> Option<T> {
+        self.slot.lock().unwrap().take()
+    }
+}
@@ -1,28 +1,53 @@
 use crate::Options;
-use dirs::cache_dir;
-use lazy_static::lazy_static;
-use log::{error, info};
-pub use report::{FloatSource, QuatSource, Source, TrackingReport, Vec2Source};
-use std::ffi::OsStr;
-use std::fs;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::mem::drop;
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use std::sync::mpsc::{sync_channel, Receiver};
-use std::thread;
-use subprocess::{ExitStatus, Popen, PopenConfig, PopenError, Redirection};
-
-const NEWLINE: u8 = "\n".as_bytes()[0];
-
-lazy_static! {
-    static ref TRACKER_BIN_PATH: PathBuf = cache_dir().unwrap().join("layertuber-tracker");
-}
-
+pub use calibration::Calibration;
+pub use face::FaceTracker;
+pub use ifacialmocap::IFacialMocapTracker;
+pub use mailbox::{mailbox, MailboxReceiver, MailboxSender};
+pub use openseeface::OpenSeeFaceTracker;
+pub use replay::{read_recording, RecordingTracker, ReplayTracker, TimedReport};
+pub use report::{
+    Channels, FieldKind, FloatSource, QuatSource, Source, TrackingReport, Vec2Source, FACE_FOUND,
+};
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::time::Instant;
+use subprocess::PopenError;
+pub use supervise::{Backoff, Tracker, TrackerControl, TrackerState};
+pub use vmc::VmcTracker;
+
+/// How to run the bundled tracker
+#[derive(Debug, Clone)]
 pub struct TrackerOptions {
     pub camera_index: u8,
     pub show_features: bool,
+
+    /// the resolution to ask the camera for. it may pick something else
+    pub capture_width: u32,
+    pub capture_height: u32,
+    pub fps: u32,
+
+    /// which of OpenSeeFace's models to use, from -3 (fastest) to 4 (best, and best at winks)
+    pub model: i8,
+
+    /// how many faces OpenSeeFace looks for. only the first is reported
+    pub max_faces: u8,
+}
+
+/// the models OpenSeeFace has
+pub const MODELS: RangeInclusive<i8> = -3..=4;
+
+impl Default for TrackerOptions {
+    fn default() -> TrackerOptions {
+        TrackerOptions {
+            camera_index: 0,
+            show_features: false,
+            capture_width: 800,
+            capture_height: 600,
+            fps: 30,
+            model: 3,
+            max_faces: 1,
+        }
+    }
 }
 
 impl From<&Options> for TrackerOptions {
@@ -30,12 +55,29 @@ impl From<&Options> for TrackerOptions {
         TrackerOptions {
             camera_index: options.camera_index,
             show_features: options.show_features,
+            capture_width: options.capture_width,
+            capture_height: options.capture_height,
+            fps: options.fps,
+            model: options.model,
+            max_faces: options.max_faces,
         }
     }
 }
 
+// debug builds run the tracker from source, so they only use this module in its tests
+#[cfg_attr(debug_assertions, allow(dead_code))]
 mod bin;
+mod calibration;
+mod face;
+mod ifacialmocap;
+mod mailbox;
+mod openseeface;
+mod osc;
+mod replay;
 mod report;
+mod supervise;
+mod udp;
+mod vmc;
 
 pub enum ControlMessage {
     Calibrate,
@@ -46,6 +88,9 @@ pub enum ControlMessage {
 pub enum RunTrackerError {
     Io(std::io::Error),
     Popen(PopenError),
+
+    /// the tracker started, but isn't one we know how to talk to
+    Incompatible(String),
 }
 
 impl From<std::io::Error> for RunTrackerError {
@@ -60,181 +105,68 @@ impl From<PopenError> for RunTrackerError {
     }
 }
 
-pub struct FaceTracker {
-    cleanup: Box<dyn FnMut()>,
-    control_rx: Receiver<ControlMessage>,
-    p: Popen,
-}
+/// Something that can produce a stream of tracking reports. `Tracker::spawn` drives one of these
+/// on its own thread, so implementations are free to block in `next_report`.
+pub trait TrackerBackend: Send {
+    /// get ready to produce reports, e.g. by starting a process or opening a file
+    fn start(&mut self) -> Result<(), RunTrackerError>;
 
-impl FaceTracker {
-    fn new(
-        p: Popen,
-        control_rx: Receiver<ControlMessage>,
-        cleanup: Box<dyn FnMut()>,
-    ) -> FaceTracker {
-        FaceTracker {
-            cleanup,
-            control_rx,
-            p,
-        }
-    }
+    /// block until the next report is available, or return `None` if there will be no more
+    fn next_report(&mut self) -> Option<TrackingReport>;
 
-    fn poll(&mut self) {
-        match self.p.poll() {
-            Some(e) => {
-                (*self.cleanup)();
-                match e {
-                    ExitStatus::Exited(s) => panic!("tracker died with exit code {}", s),
-                    ExitStatus::Signaled(s) => panic!("tracker died with signal {}", s),
-                    ExitStatus::Other(s) => panic!("tracker died for some reason: {}", s),
-                    ExitStatus::Undetermined => panic!("tracker died for some reason"),
-                }
-            }
-            None => (),
-        };
-    }
+    /// handle a message from the renderer
+    fn send(&mut self, message: ControlMessage);
 
-    fn handle_input(&mut self) {
-        while let Ok(cm) = self.control_rx.try_recv() {
-            match cm {
-                ControlMessage::Calibrate => {
-                    self.p
-                        .stdin
-                        .as_ref()
-                        .unwrap()
-                        .write_all("calibrate\n".as_bytes())
-                        .unwrap();
-                }
-                ControlMessage::Die => {
-                    panic!("this should quit gracefully")
-                }
-            }
-        }
+    /// whether the last `None` from `next_report` was because something went wrong, rather than
+    /// because the backend ran out of reports or was told to stop. failed backends get restarted
+    fn failed(&self) -> bool {
+        false
     }
 
-    fn read_line(&mut self) -> String {
-        let mut line = String::new();
-
-        loop {
-            let mut one_char_buf: [u8; 1] = [0];
-
-            match self.p.stdout.as_ref().unwrap().read(&mut one_char_buf) {
-                Ok(size) => {
-                    if size != 1 {
-                        panic!("got {}-length read when line was: {}", size, line)
-                    }
-                    if one_char_buf == [NEWLINE] {
-                        break;
-                    }
-                    line.push(one_char_buf[0] as char);
-                }
-                Err(e) => {
-                    (*self.cleanup)();
-                    panic!("no tracking report: {}", e);
-                }
-            }
-        }
-
-        self.p
-            .stdin
-            .as_ref()
-            .unwrap()
-            .write_all(&[NEWLINE])
-            .unwrap();
-
-        line
+    /// something another thread can call to make a blocked `next_report` return soon, so that
+    /// stopping doesn't have to wait for the next report. called after `start`
+    fn interrupter(&self) -> Option<Interrupter> {
+        None
     }
-}
-
-impl Iterator for FaceTracker {
-    type Item = TrackingReport;
-
-    fn next(&mut self) -> Option<TrackingReport> {
-        loop {
-            self.poll();
-            self.handle_input();
-
-            let line = self.read_line();
 
-            if !line.is_empty() {
-                let report: TrackingReport = match serde_json::from_str(&line) {
-                    Ok(r) => r,
-                    Err(e) => panic!("got bad data from tracker: {} ({})", line, e),
-                };
-                return Some(report);
-            }
-        }
-    }
+    /// release anything acquired in `start`
+    fn shutdown(&mut self);
 }
 
-pub fn run_tracker(
-    control_rx: Receiver<ControlMessage>,
-    options: &TrackerOptions,
-) -> Result<FaceTracker, RunTrackerError> {
-    let mut tracker_bin = File::create(TRACKER_BIN_PATH.as_path())?;
-
-    #[cfg(not(debug_assertions))]
-    tracker_bin.write_all(bin::TRACKER_BIN)?;
-
-    tracker_bin.flush()?;
-
-    let metadata = tracker_bin.metadata()?;
-    let mut permissions = metadata.permissions();
-    permissions.set_mode(0o700);
-    fs::set_permissions(TRACKER_BIN_PATH.as_path(), permissions)?;
-
-    drop(tracker_bin);
-
-    let mut args: Vec<&OsStr> = Vec::new();
-
-    #[cfg(not(debug_assertions))]
-    args.push(TRACKER_BIN_PATH.as_path().as_os_str());
-
-    #[cfg(debug_assertions)]
-    args.extend(["python", "src/py/layertuber/__init__.py"].map(OsStr::new));
-
-    let camera_index = format!("--camera={}", options.camera_index);
-    args.push(OsStr::new(&camera_index));
-
-    if options.show_features {
-        args.push(OsStr::new("--show-features"))
+/// pick a backend based on the command line or OBS options
+pub fn backend_from_options(options: &Options) -> Box<dyn TrackerBackend> {
+    let backend: Box<dyn TrackerBackend> = if let Some(path) = &options.replay {
+        Box::new(ReplayTracker::new(path.clone(), options.replay_speed))
+    } else if let Some(port) = options.openseeface {
+        Box::new(OpenSeeFaceTracker::new(
+            port,
+            Calibration::load("openseeface"),
+        ))
+    } else if let Some(port) = options.vmc {
+        Box::new(VmcTracker::new(port, Calibration::load("vmc")))
+    } else if let Some(phone) = options.ifacialmocap {
+        Box::new(IFacialMocapTracker::new(
+            SocketAddr::new(phone, ifacialmocap::PORT),
+            ifacialmocap::PORT,
+            Calibration::load("ifacialmocap"),
+        ))
+    } else {
+        Box::new(FaceTracker::new(
+            TrackerOptions::from(options),
+            Calibration::load("face"),
+        ))
+    };
+
+    match &options.record {
+        Some(path) => Box::new(RecordingTracker::new(backend, path.clone())),
+        None => backend,
     }
-
-    let p = Popen::create(
-        &args,
-        PopenConfig {
-            stdin: Redirection::Pipe,
-            stdout: Redirection::Pipe,
-            ..Default::default()
-        },
-    )?;
-
-    let tracker = FaceTracker::new(
-        p,
-        control_rx,
-        Box::new(|| {
-            match fs::remove_file(TRACKER_BIN_PATH.as_path()) {
-                Ok(_) => info!("deleted tracker"),
-                Err(e) => error!("tracker deletion failed: {}", e),
-            };
-        }),
-    );
-
-    Ok(tracker)
 }
 
-pub fn spawn_tracker(
-    options: TrackerOptions,
-    control_rx: Receiver<ControlMessage>,
-) -> (Receiver<TrackingReport>, thread::JoinHandle<()>) {
-    let (report_tx, report_rx) = sync_channel(0);
-    (
-        report_rx,
-        thread::spawn(move || {
-            let tracker = run_tracker(control_rx, &options).expect("could not start tracker");
-            for report in tracker {
-                report_tx.send(report).unwrap()
-            }
-        }),
-    )
+pub type Interrupter = Box<dyn Fn() + Send>;
+
+/// A report, along with when it came out of the tracker
+pub struct StampedReport {
+    pub received: Instant,
+    pub report: TrackingReport,
 }
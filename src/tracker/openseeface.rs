@@ -0,0 +1,269 @@
+//! receiving the packets a standalone OpenSeeFace sends over UDP, and deriving the same channels
+//! from them as src/py/layertuber/tracking/face.py does
+
+use super::calibration::Calibration;
+use super::report::FACE_FOUND;
+use super::udp::UdpListener;
+use super::{ControlMessage, Interrupter, RunTrackerError, TrackerBackend, TrackingReport};
+use log::error;
+
+/// landmarks per face: 66 points on the face, then one for each pupil. the first pupil is the one
+/// the bundled tracker reports as `left_gaze` (it's `eye_state[0]` there), so we do too, so that
+/// rigs behave the same whichever one they're tracked with
+const LANDMARKS: usize = 68;
+const POINTS_3D: usize = 70;
+const FEATURES: usize = 14;
+
+/// the size of one face in a packet. a packet may hold several faces, one after the other
+const FACE_SIZE: usize = 8 // time
+    + 4 // id
+    + 4 * 2 // camera resolution
+    + 4 * 2 // eye blink
+    + 1 // success
+    + 4 // pnp error
+    + 4 * 4 // quaternion
+    + 4 * 3 // euler angles
+    + 4 * 3 // translation
+    + 4 * LANDMARKS // landmark confidence
+    + 4 * 2 * LANDMARKS // landmarks
+    + 4 * 3 * POINTS_3D // 3d points
+    + 4 * FEATURES;
+
+/// the parts of one face from an OpenSeeFace packet that we use
+#[derive(Debug, Clone, PartialEq)]
+pub struct Face {
+    pub width: f32,
+    pub height: f32,
+
+    /// whether OpenSeeFace managed to fit its model to the face
+    pub success: bool,
+
+    /// how open each eye is, in the order the bundled tracker calls left and right
+    pub eye_blink: [f32; 2],
+    pub quaternion: [f32; 4],
+
+    /// in pixels, x then y
+    pub landmarks: Vec<[f32; 2]>,
+    pub features: Features,
+}
+
+/// OpenSeeFace's facial features, in the order it sends them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Features {
+    pub eye_left: f32,
+    pub eye_right: f32,
+    pub eyebrow_steepness_left: f32,
+    pub eyebrow_updown_left: f32,
+    pub eyebrow_quirk_left: f32,
+    pub eyebrow_steepness_right: f32,
+    pub eyebrow_updown_right: f32,
+    pub eyebrow_quirk_right: f32,
+    pub mouth_corner_updown_left: f32,
+    pub mouth_corner_inout_left: f32,
+    pub mouth_corner_updown_right: f32,
+    pub mouth_corner_inout_right: f32,
+    pub mouth_open: f32,
+    pub mouth_wide: f32,
+}
+
+/// reads little-endian values from the front of a packet
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> &'a [u8] {
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        taken
+    }
+
+    fn skip(&mut self, n: usize) {
+        self.take(n);
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn f32s<const N: usize>(&mut self) -> [f32; N] {
+        [(); N].map(|_| self.f32())
+    }
+}
+
+impl Face {
+    /// parse the first face in a packet
+    pub fn parse(packet: &[u8]) -> Result<Face, String> {
+        if packet.len() < FACE_SIZE {
+            return Err(format!(
+                "packet is {} bytes, but a face takes {}",
+                packet.len(),
+                FACE_SIZE
+            ));
+        }
+
+        let mut cursor = Cursor { bytes: packet };
+
+        cursor.skip(8 + 4); // time and id
+        let [width, height] = cursor.f32s();
+        let eye_blink = cursor.f32s();
+        let success = cursor.take(1)[0] != 0;
+        cursor.skip(4); // pnp error
+        let quaternion = cursor.f32s();
+        cursor.skip(4 * 3 + 4 * 3); // euler angles and translation
+        cursor.skip(4 * LANDMARKS); // confidence
+        let landmarks = (0..LANDMARKS).map(|_| cursor.f32s()).collect();
+        cursor.skip(4 * 3 * POINTS_3D);
+
+        Ok(Face {
+            width,
+            height,
+            success,
+            eye_blink,
+            quaternion,
+            landmarks,
+            // fields are read in the order they're written here
+            features: Features {
+                eye_left: cursor.f32(),
+                eye_right: cursor.f32(),
+                eyebrow_steepness_left: cursor.f32(),
+                eyebrow_updown_left: cursor.f32(),
+                eyebrow_quirk_left: cursor.f32(),
+                eyebrow_steepness_right: cursor.f32(),
+                eyebrow_updown_right: cursor.f32(),
+                eyebrow_quirk_right: cursor.f32(),
+                mouth_corner_updown_left: cursor.f32(),
+                mouth_corner_inout_left: cursor.f32(),
+                mouth_corner_updown_right: cursor.f32(),
+                mouth_corner_inout_right: cursor.f32(),
+                mouth_open: cursor.f32(),
+                mouth_wide: cursor.f32(),
+            },
+        })
+    }
+
+    /// a point in pixels, as an offset from the middle of the frame, where the edges are ±0.5
+    fn offset(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [x / self.width - 0.5, y / self.height - 0.5]
+    }
+
+    /// the channels the bundled tracker would have reported for this face, before calibration
+    pub fn report(&self) -> TrackingReport {
+        if !self.success {
+            return TrackingReport::lost();
+        }
+
+        let f = &self.features;
+        let mut report = TrackingReport::default();
+
+        let floats = [
+            ("blink_left", self.eye_blink[0]),
+            ("blink_right", self.eye_blink[1]),
+            ("blink", (self.eye_blink[0] + self.eye_blink[1]) / 2.0),
+            ("eyebrow_quirk_left", f.eyebrow_quirk_left),
+            ("eyebrow_quirk_right", f.eyebrow_quirk_right),
+            (
+                "eyebrow_quirk",
+                (f.eyebrow_quirk_left + f.eyebrow_quirk_right) / 2.0,
+            ),
+            ("eyebrow_steepness_left", f.eyebrow_steepness_left),
+            ("eyebrow_steepness_right", f.eyebrow_steepness_right),
+            (
+                "eyebrow_steepness",
+                (f.eyebrow_steepness_left + f.eyebrow_steepness_right) / 2.0,
+            ),
+            ("eyebrow_updown_left", f.eyebrow_updown_left),
+            ("eyebrow_updown_right", f.eyebrow_updown_right),
+            (
+                "eyebrow_updown",
+                (f.eyebrow_updown_left + f.eyebrow_updown_right) / 2.0,
+            ),
+            ("mouth_open", f.mouth_open),
+            ("mouth_wide", f.mouth_wide),
+            (FACE_FOUND, 1.0),
+        ];
+        for (name, value) in floats {
+            report.floats.insert(name.to_string(), value);
+        }
+
+        let count = self.landmarks.len() as f32;
+        let middle = self.landmarks.iter().fold([0.0, 0.0], |[x, y], [lx, ly]| {
+            [x + lx / count, y + ly / count]
+        });
+        let left_gaze = self.offset(self.landmarks[LANDMARKS - 2]);
+        let right_gaze = self.offset(self.landmarks[LANDMARKS - 1]);
+
+        let vec2s = [
+            ("face_position", self.offset(middle)),
+            ("left_gaze", left_gaze),
+            ("right_gaze", right_gaze),
+            (
+                "gaze",
+                [
+                    (left_gaze[0] + right_gaze[0]) / 2.0,
+                    (left_gaze[1] + right_gaze[1]) / 2.0,
+                ],
+            ),
+        ];
+        for (name, value) in vec2s {
+            report.vec2s.insert(name.to_string(), value);
+        }
+
+        report
+            .rotations
+            .insert("head_rotation".to_string(), self.quaternion);
+
+        report
+    }
+}
+
+/// Listens for packets from an OpenSeeFace that someone is running themselves, perhaps on another
+/// machine
+pub struct OpenSeeFaceTracker {
+    listener: UdpListener,
+
+    calibration: Calibration,
+}
+
+impl OpenSeeFaceTracker {
+    pub fn new(port: u16, calibration: Calibration) -> OpenSeeFaceTracker {
+        OpenSeeFaceTracker {
+            listener: UdpListener::new("OpenSeeFace", port),
+            calibration,
+        }
+    }
+}
+
+impl TrackerBackend for OpenSeeFaceTracker {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        Ok(self.listener.bind()?)
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        loop {
+            match Face::parse(self.listener.recv()?) {
+                Ok(face) => return Some(self.calibration.apply(face.report())),
+                Err(e) => error!("got a bad packet from OpenSeeFace: {}", e),
+            }
+        }
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        match message {
+            ControlMessage::Calibrate => self.calibration.reset(),
+            ControlMessage::Die => self.listener.stop(),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.listener.failed()
+    }
+
+    fn interrupter(&self) -> Option<Interrupter> {
+        Some(self.listener.interrupter())
+    }
+
+    fn shutdown(&mut self) {
+        self.listener.close();
+    }
+}
@@ -0,0 +1,143 @@
+//! just enough of Open Sound Control to read the messages VMC senders use
+
+/// An argument to an OSC message. 64-bit numbers are narrowed, since we have no use for the
+/// precision
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Bool(bool),
+    Nil,
+}
+
+impl OscArg {
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            OscArg::Float(f) => Some(*f),
+            OscArg::Int(i) => Some(*i as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            OscArg::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+/// reads big-endian, 4-byte aligned OSC values from the front of a packet
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.bytes.len() {
+            return Err(format!(
+                "wanted {} more bytes, but there are only {}",
+                n,
+                self.bytes.len()
+            ));
+        }
+
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    /// a string is null-terminated, then padded to a multiple of 4 bytes
+    fn string(&mut self) -> Result<String, String> {
+        let len = self
+            .bytes
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or("unterminated string")?;
+        let string = String::from_utf8_lossy(self.take(len)?).into_owned();
+        self.take(4 - len % 4)?;
+        Ok(string)
+    }
+
+    fn blob(&mut self) -> Result<&'a [u8], String> {
+        let len = i32::from_be_bytes(self.array()?) as usize;
+        let blob = self.take(len)?;
+        self.take((4 - len % 4) % 4)?;
+        Ok(blob)
+    }
+}
+
+fn parse_message(bytes: &[u8]) -> Result<OscMessage, String> {
+    let mut cursor = Cursor { bytes };
+    let address = cursor.string()?;
+
+    // very old senders leave out the type tags, but nothing that speaks VMC does
+    let tags = cursor.string()?;
+    let tags = tags
+        .strip_prefix(',')
+        .ok_or_else(|| format!("{} has no type tags", address))?;
+
+    let mut args = Vec::new();
+    for tag in tags.chars() {
+        args.push(match tag {
+            'i' => OscArg::Int(i32::from_be_bytes(cursor.array()?)),
+            'f' => OscArg::Float(f32::from_be_bytes(cursor.array()?)),
+            'h' => OscArg::Int(i64::from_be_bytes(cursor.array()?) as i32),
+            'd' => OscArg::Float(f64::from_be_bytes(cursor.array()?) as f32),
+            's' | 'S' => OscArg::String(cursor.string()?),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            'N' | 'I' => OscArg::Nil,
+            'b' => {
+                cursor.blob()?;
+                OscArg::Nil
+            }
+            't' => {
+                cursor.take(8)?;
+                OscArg::Nil
+            }
+            'c' | 'r' | 'm' => {
+                cursor.take(4)?;
+                OscArg::Nil
+            }
+            other => return Err(format!("{} has an unknown type tag `{}`", address, other)),
+        });
+    }
+
+    Ok(OscMessage { address, args })
+}
+
+/// every message in a packet, in order, with any bundles unpacked
+pub fn parse_packet(bytes: &[u8]) -> Result<Vec<OscMessage>, String> {
+    let mut messages = Vec::new();
+    parse_into(bytes, &mut messages)?;
+    Ok(messages)
+}
+
+fn parse_into(bytes: &[u8], messages: &mut Vec<OscMessage>) -> Result<(), String> {
+    if !bytes.starts_with(b"#bundle\0") {
+        messages.push(parse_message(bytes)?);
+        return Ok(());
+    }
+
+    let mut cursor = Cursor { bytes };
+    cursor.take(8 + 8)?; // "#bundle" and the time tag, which we ignore
+
+    while !cursor.bytes.is_empty() {
+        let element = cursor.blob()?;
+        parse_into(element, messages)?;
+    }
+
+    Ok(())
+}
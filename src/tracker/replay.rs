@@ -0,0 +1,249 @@
+use super::{ControlMessage, Interrupter, RunTrackerError, TrackerBackend, TrackingReport};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// One line of a recording: a report, and how many seconds into the recording it arrived. The
+/// report fields sit alongside `time`, so a recording is also valid tracker output.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimedReport {
+    #[serde(default)]
+    pub time: f64,
+
+    #[serde(flatten)]
+    pub report: TrackingReport,
+}
+
+/// whether `line` is the hello that starts captured tracker output, which isn't a report
+fn is_hello(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line).is_ok_and(|v| v.get("hello").is_some())
+}
+
+/// read a whole recording (or captured tracker output) into memory
+pub fn read_recording(path: &Path) -> io::Result<Vec<TimedReport>> {
+    let mut reports = Vec::new();
+
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || is_hello(&line) {
+            continue;
+        }
+
+        reports.push(serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", path.display(), index + 1, e),
+            )
+        })?);
+    }
+
+    Ok(reports)
+}
+
+/// Passes through every report from another backend, writing each one to a file as it goes
+pub struct RecordingTracker {
+    inner: Box<dyn TrackerBackend>,
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+    started: Instant,
+
+    /// whether we've started before, and so have a recording to add to
+    recording: bool,
+}
+
+impl RecordingTracker {
+    pub fn new(inner: Box<dyn TrackerBackend>, path: PathBuf) -> RecordingTracker {
+        RecordingTracker {
+            inner,
+            path,
+            file: None,
+            started: Instant::now(),
+            recording: false,
+        }
+    }
+
+    fn record(&mut self, report: TrackingReport) -> TrackingReport {
+        let timed = TimedReport {
+            time: self.started.elapsed().as_secs_f64(),
+            report,
+        };
+
+        if let Some(file) = &mut self.file {
+            let written = serde_json::to_writer(&mut *file, &timed)
+                .map_err(io::Error::from)
+                .and_then(|_| file.write_all(b"\n"));
+
+            if let Err(e) = written {
+                error!("could not write to recording, stopping: {}", e);
+                self.file = None;
+            }
+        }
+
+        timed.report
+    }
+}
+
+impl TrackerBackend for RecordingTracker {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        // if we're being restarted, carry on with the same recording rather than replacing it
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(self.recording)
+                .truncate(!self.recording)
+                .open(&self.path)?;
+            self.file = Some(BufWriter::new(file));
+        }
+
+        self.inner.start()?;
+
+        if !self.recording {
+            self.started = Instant::now();
+            self.recording = true;
+            info!("recording to {}", self.path.display());
+        }
+        Ok(())
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        let report = self.inner.next_report()?;
+        Some(self.record(report))
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        self.inner.send(message)
+    }
+
+    fn failed(&self) -> bool {
+        self.inner.failed()
+    }
+
+    fn interrupter(&self) -> Option<Interrupter> {
+        self.inner.interrupter()
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown();
+
+        if let Some(mut file) = self.file.take() {
+            if let Err(e) = file.flush() {
+                error!("could not finish writing recording: {}", e);
+            }
+        }
+    }
+}
+
+/// Plays back a file written by `RecordingTracker`, at `speed` times the original rate
+pub struct ReplayTracker {
+    path: PathBuf,
+    speed: f32,
+    lines: Option<Lines<BufReader<File>>>,
+    started: Instant,
+
+    // shared with the interrupter, which wakes us from waiting for the next report to be due
+    stopping: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ReplayTracker {
+    pub fn new(path: PathBuf, speed: f32) -> ReplayTracker {
+        ReplayTracker {
+            path,
+            speed,
+            lines: None,
+            started: Instant::now(),
+            stopping: Arc::new((Mutex::new(false), Condvar::new())),
+        }
+    }
+
+    /// wait until `due`, returning false if we were told to stop first
+    fn wait_until(&self, due: Instant) -> bool {
+        let (stopping, wake) = &*self.stopping;
+        let mut stopping = stopping.lock().unwrap();
+
+        while !*stopping {
+            match due.checked_duration_since(Instant::now()) {
+                Some(wait) => stopping = wake.wait_timeout(stopping, wait).unwrap().0,
+                None => return true,
+            }
+        }
+
+        false
+    }
+
+    fn next_timed_report(&mut self) -> Option<TimedReport> {
+        let lines = self.lines.as_mut()?;
+
+        loop {
+            let line = match lines.next()? {
+                Ok(l) => l,
+                Err(e) => {
+                    error!("could not read from {}: {}", self.path.display(), e);
+                    return None;
+                }
+            };
+
+            if line.is_empty() || is_hello(&line) {
+                continue;
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(r) => return Some(r),
+                Err(e) => error!("skipping bad line in recording: {} ({})", line, e),
+            }
+        }
+    }
+}
+
+impl TrackerBackend for ReplayTracker {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        self.lines = Some(BufReader::new(File::open(&self.path)?).lines());
+        self.started = Instant::now();
+        *self.stopping.0.lock().unwrap() = false;
+        info!("replaying {}", self.path.display());
+        Ok(())
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        let timed = self.next_timed_report()?;
+
+        if self.speed > 0.0 {
+            // a time we can't wait until, like one before the recording started, is due now
+            let offset = Duration::try_from_secs_f64(timed.time / self.speed as f64).ok();
+            let due = offset.and_then(|offset| self.started.checked_add(offset));
+            if let Some(due) = due {
+                if !self.wait_until(due) {
+                    return None;
+                }
+            }
+        }
+
+        Some(timed.report)
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        match message {
+            ControlMessage::Calibrate => info!("calibration does nothing during a replay"),
+            ControlMessage::Die => self.lines = None,
+        }
+    }
+
+    fn interrupter(&self) -> Option<Interrupter> {
+        let stopping = self.stopping.clone();
+
+        Some(Box::new(move || {
+            let (stopping, wake) = &*stopping;
+            *stopping.lock().unwrap() = true;
+            wake.notify_all();
+        }))
+    }
+
+    fn shutdown(&mut self) {
+        self.lines = None;
+    }
+}
@@ -1,90 +1,444 @@
 /**
-The types here must reflect the types defined in tracking/report.py
+A report is a set of named channels, each holding a float, a 2d vector or a rotation. The tracker
+announces its channels (from tracking/report.py) when it starts, and `check_fields` compares them
+with the standard channels here
 */
-use serde::Deserialize;
+use log::{info, warn};
+use serde::de::Deserializer;
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::ops::Mul;
-use three_d::{Quaternion, Vector2, Vector3};
+use three_d::{InnerSpace, Quaternion, Vector2, Vector3};
 
-#[derive(Deserialize, Debug)]
+const REST_FLOAT: f32 = 0.0;
+const REST_VEC2: [f32; 2] = [0.0, 0.0];
+const REST_ROTATION: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// the channel that says whether the tracker can see a face: 1 if it can, 0 if it can't
+pub const FACE_FOUND: &str = "face_found";
+
+/// the value a float channel has when your face is at rest. that's 0 for most, but the bundled
+/// tracker's blinks measure how open your eyes are
+fn resting_float(name: &str) -> f32 {
+    match name {
+        "blink" | "blink_left" | "blink_right" | FACE_FOUND => 1.0,
+        _ => REST_FLOAT,
+    }
+}
+
+/// Everything a tracker saw in one frame. Channels a tracker doesn't report are left at rest.
+///
+/// On the wire (and in recordings) this is a flat JSON object; the kind of each channel is
+/// worked out from its value.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct TrackingReport {
-    pub blink: f32,
-    pub blink_left: f32,
-    pub blink_right: f32,
-    pub eyebrow_quirk: f32,
-    pub eyebrow_quirk_left: f32,
-    pub eyebrow_quirk_right: f32,
-    pub eyebrow_steepness: f32,
-    pub eyebrow_steepness_left: f32,
-    pub eyebrow_steepness_right: f32,
-    pub eyebrow_updown: f32,
-    pub eyebrow_updown_left: f32,
-    pub eyebrow_updown_right: f32,
-    pub mouth_open: f32,
-    pub mouth_wide: f32,
-
-    pub head_rotation: [f32; 4],
-
-    pub face_position: [f32; 2],
-    pub left_gaze: [f32; 2],
-    pub right_gaze: [f32; 2],
-    pub gaze: [f32; 2],
+    pub floats: HashMap<String, f32>,
+    pub vec2s: HashMap<String, [f32; 2]>,
+    pub rotations: HashMap<String, [f32; 4]>,
 }
 
-pub trait Source<T> {
-    fn value(&self, report: &TrackingReport) -> T;
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ChannelValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Rotation([f32; 4]),
 }
 
-#[derive(Debug, Deserialize, Copy, Clone)]
+impl Serialize for TrackingReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // sorted, so that recordings are easy to read and compare
+        let values: BTreeMap<&str, ChannelValue> = self
+            .floats
+            .iter()
+            .map(|(k, v)| (k.as_str(), ChannelValue::Float(*v)))
+            .chain(
+                self.vec2s
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), ChannelValue::Vec2(*v))),
+            )
+            .chain(
+                self.rotations
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), ChannelValue::Rotation(*v))),
+            )
+            .collect();
+
+        let mut map = serializer.serialize_map(Some(values.len()))?;
+        for (k, v) in values {
+            map.serialize_entry(k, &v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackingReport {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TrackingReport, D::Error> {
+        let values = HashMap::<String, ChannelValue>::deserialize(deserializer)?;
+        let mut report = TrackingReport::default();
+
+        for (name, value) in values {
+            match value {
+                ChannelValue::Float(v) => {
+                    report.floats.insert(name, v);
+                }
+                ChannelValue::Vec2(v) => {
+                    report.vec2s.insert(name, v);
+                }
+                ChannelValue::Rotation(v) => {
+                    report.rotations.insert(name, v);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// the kinds of value a channel can hold, named as in tracking/report.py
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-pub enum FloatSource {
-    Blink,
-    BlinkLeft,
-    BlinkRight,
-    EyebrowQuirk,
-    EyebrowQuirkLeft,
-    EyebrowQuirkRight,
-    EyebrowSteepness,
-    EyebrowSteepnessLeft,
-    EyebrowSteepnessRight,
-    EyebrowUpdown,
-    EyebrowUpdownLeft,
-    EyebrowUpdownRight,
-    MouthOpen,
-    MouthWide,
+pub enum FieldKind {
+    Float,
+    Rotation,
+    Vec2,
 }
 
-impl Source<f32> for FloatSource {
-    fn value(&self, report: &TrackingReport) -> f32 {
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            FloatSource::Blink => report.blink,
-            FloatSource::BlinkLeft => report.blink_left,
-            FloatSource::BlinkRight => report.blink_right,
-            FloatSource::EyebrowQuirk => report.eyebrow_quirk,
-            FloatSource::EyebrowQuirkLeft => report.eyebrow_quirk_left,
-            FloatSource::EyebrowQuirkRight => report.eyebrow_quirk_right,
-            FloatSource::EyebrowSteepness => report.eyebrow_steepness,
-            FloatSource::EyebrowSteepnessLeft => report.eyebrow_steepness_left,
-            FloatSource::EyebrowSteepnessRight => report.eyebrow_steepness_right,
-            FloatSource::EyebrowUpdown => report.eyebrow_updown,
-            FloatSource::EyebrowUpdownLeft => report.eyebrow_updown_left,
-            FloatSource::EyebrowUpdownRight => report.eyebrow_updown_right,
-            FloatSource::MouthOpen => report.mouth_open,
-            FloatSource::MouthWide => report.mouth_wide,
+            FieldKind::Float => write!(f, "float"),
+            FieldKind::Rotation => write!(f, "rotation"),
+            FieldKind::Vec2 => write!(f, "vec2"),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Copy, Clone)]
-#[serde(rename_all = "snake_case")]
-pub enum QuatSource {
-    HeadRotation,
+/// the channels the bundled tracker reports, which any rig can refer to
+const STANDARD_CHANNELS: &[(&str, FieldKind)] = &[
+    ("blink", FieldKind::Float),
+    ("blink_left", FieldKind::Float),
+    ("blink_right", FieldKind::Float),
+    ("eyebrow_quirk", FieldKind::Float),
+    ("eyebrow_quirk_left", FieldKind::Float),
+    ("eyebrow_quirk_right", FieldKind::Float),
+    ("eyebrow_steepness", FieldKind::Float),
+    ("eyebrow_steepness_left", FieldKind::Float),
+    ("eyebrow_steepness_right", FieldKind::Float),
+    ("eyebrow_updown", FieldKind::Float),
+    ("eyebrow_updown_left", FieldKind::Float),
+    ("eyebrow_updown_right", FieldKind::Float),
+    ("mouth_open", FieldKind::Float),
+    ("mouth_wide", FieldKind::Float),
+    (FACE_FOUND, FieldKind::Float),
+    ("head_rotation", FieldKind::Rotation),
+    ("face_position", FieldKind::Vec2),
+    ("left_gaze", FieldKind::Vec2),
+    ("right_gaze", FieldKind::Vec2),
+    ("gaze", FieldKind::Vec2),
+];
+
+/// the blendshapes ARKit tracks, which phone-based trackers report as floats with these names
+pub const ARKIT_BLENDSHAPES: &[&str] = &[
+    "browDownLeft",
+    "browDownRight",
+    "browInnerUp",
+    "browOuterUpLeft",
+    "browOuterUpRight",
+    "cheekPuff",
+    "cheekSquintLeft",
+    "cheekSquintRight",
+    "eyeBlinkLeft",
+    "eyeBlinkRight",
+    "eyeLookDownLeft",
+    "eyeLookDownRight",
+    "eyeLookInLeft",
+    "eyeLookInRight",
+    "eyeLookOutLeft",
+    "eyeLookOutRight",
+    "eyeLookUpLeft",
+    "eyeLookUpRight",
+    "eyeSquintLeft",
+    "eyeSquintRight",
+    "eyeWideLeft",
+    "eyeWideRight",
+    "jawForward",
+    "jawLeft",
+    "jawOpen",
+    "jawRight",
+    "mouthClose",
+    "mouthDimpleLeft",
+    "mouthDimpleRight",
+    "mouthFrownLeft",
+    "mouthFrownRight",
+    "mouthFunnel",
+    "mouthLeft",
+    "mouthLowerDownLeft",
+    "mouthLowerDownRight",
+    "mouthPressLeft",
+    "mouthPressRight",
+    "mouthPucker",
+    "mouthRight",
+    "mouthRollLower",
+    "mouthRollUpper",
+    "mouthShrugLower",
+    "mouthShrugUpper",
+    "mouthSmileLeft",
+    "mouthSmileRight",
+    "mouthStretchLeft",
+    "mouthStretchRight",
+    "mouthUpperUpLeft",
+    "mouthUpperUpRight",
+    "noseSneerLeft",
+    "noseSneerRight",
+    "tongueOut",
+];
+
+/// A set of channel names of each kind
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Channels {
+    #[serde(default)]
+    pub floats: BTreeSet<String>,
+
+    #[serde(default)]
+    pub vec2s: BTreeSet<String>,
+
+    #[serde(default)]
+    pub rotations: BTreeSet<String>,
+}
+
+impl Channels {
+    /// the channels any rig can refer to without declaring them: the bundled tracker's, and
+    /// ARKit's blendshapes
+    pub fn standard() -> Channels {
+        let mut channels = Channels::default();
+        for (name, kind) in STANDARD_CHANNELS {
+            channels.insert(name, *kind);
+        }
+        for name in ARKIT_BLENDSHAPES {
+            channels.insert(name, FieldKind::Float);
+        }
+        channels
+    }
+
+    pub fn insert(&mut self, name: &str, kind: FieldKind) {
+        match kind {
+            FieldKind::Float => self.floats.insert(name.to_string()),
+            FieldKind::Vec2 => self.vec2s.insert(name.to_string()),
+            FieldKind::Rotation => self.rotations.insert(name.to_string()),
+        };
+    }
+
+    pub fn kind_of(&self, name: &str) -> Option<FieldKind> {
+        if self.floats.contains(name) {
+            Some(FieldKind::Float)
+        } else if self.vec2s.contains(name) {
+            Some(FieldKind::Vec2)
+        } else if self.rotations.contains(name) {
+            Some(FieldKind::Rotation)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, FieldKind)> {
+        self.floats
+            .iter()
+            .map(|n| (n.as_str(), FieldKind::Float))
+            .chain(self.vec2s.iter().map(|n| (n.as_str(), FieldKind::Vec2)))
+            .chain(
+                self.rotations
+                    .iter()
+                    .map(|n| (n.as_str(), FieldKind::Rotation)),
+            )
+    }
+}
+
+/// compare the channels a tracker says it will report with the standard ones. missing and extra
+/// channels are logged, but a standard channel with the wrong kind of value is an error naming
+/// every channel that doesn't match
+pub fn check_fields(announced: &HashMap<String, FieldKind>) -> Result<(), String> {
+    let mut problems = Vec::new();
+
+    for (name, expected) in STANDARD_CHANNELS {
+        match announced.get(*name) {
+            Some(kind) if kind == expected => (),
+            Some(kind) => problems.push(format!(
+                "`{}` should be a {}, not a {}",
+                name, expected, kind
+            )),
+            None => warn!("tracker does not report `{}`; it will stay at rest", name),
+        }
+    }
+
+    for (name, kind) in announced {
+        if !STANDARD_CHANNELS.iter().any(|(n, _)| n == name) {
+            info!("tracker also reports `{}`, a {}", name, kind);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "tracker fields don't match: {}",
+            problems.join(", ")
+        ))
+    }
+}
+
+impl TrackingReport {
+    /// what a tracker reports when it can't see a face
+    pub fn lost() -> TrackingReport {
+        let mut report = TrackingReport::default();
+        report.floats.insert(FACE_FOUND.to_string(), 0.0);
+        report
+    }
+
+    /// whether the tracker could see a face. trackers that can't tell are assumed to, since
+    /// `face_found` rests at 1
+    pub fn face_found(&self) -> bool {
+        let found = self.floats.get(FACE_FOUND).copied();
+        found.unwrap_or_else(|| resting_float(FACE_FOUND)) >= 0.5
+    }
+
+    /// a report with the same channels as this one, all at rest
+    pub fn resting(&self) -> TrackingReport {
+        TrackingReport {
+            floats: self
+                .floats
+                .keys()
+                .map(|name| (name.clone(), resting_float(name)))
+                .collect(),
+            vec2s: self
+                .vec2s
+                .keys()
+                .map(|name| (name.clone(), REST_VEC2))
+                .collect(),
+            rotations: self
+                .rotations
+                .keys()
+                .map(|name| (name.clone(), REST_ROTATION))
+                .collect(),
+        }
+    }
+
+    /// the report `t` of the way from `self` to `other`. rotations are slerped, everything else
+    /// is linear. `t` may be outside 0..1 to extrapolate. channels that only `other` has are
+    /// taken from `other` as they are
+    pub fn interpolate(&self, other: &TrackingReport, t: f32) -> TrackingReport {
+        TrackingReport {
+            floats: interpolate_map(&self.floats, &other.floats, |a, b| lerp(a, b, t)),
+            vec2s: interpolate_map(&self.vec2s, &other.vec2s, |a, b| lerp2(a, b, t)),
+            rotations: interpolate_map(&self.rotations, &other.rotations, |a, b| slerp(a, b, t)),
+        }
+    }
+
+    /// this report, made relative to `neutral`: positions and rotations are measured from
+    /// neutral ones, and floats are shifted so that their neutral values become their resting ones
+    pub fn relative_to(mut self, neutral: &TrackingReport) -> TrackingReport {
+        for (name, value) in self.floats.iter_mut() {
+            if let Some(n) = neutral.floats.get(name) {
+                *value += resting_float(name) - n;
+            }
+        }
+
+        for (name, value) in self.vec2s.iter_mut() {
+            if let Some(n) = neutral.vec2s.get(name) {
+                *value = [value[0] - n[0], value[1] - n[1]];
+            }
+        }
+
+        for (name, value) in self.rotations.iter_mut() {
+            if let Some(n) = neutral.rotations.get(name) {
+                let q = Quaternion::new(value[3], value[0], value[1], value[2])
+                    * Quaternion::new(n[3], -n[0], -n[1], -n[2]);
+                *value = [q.v.x, q.v.y, q.v.z, q.s];
+            }
+        }
+
+        self
+    }
+}
+
+fn interpolate_map<T: Copy>(
+    from: &HashMap<String, T>,
+    to: &HashMap<String, T>,
+    f: impl Fn(T, T) -> T,
+) -> HashMap<String, T> {
+    to.iter()
+        .map(|(name, b)| {
+            let value = match from.get(name) {
+                Some(a) => f(*a, *b),
+                None => *b,
+            };
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
 }
 
+fn lerp2(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [lerp(a[0], b[0], t), lerp(a[1], b[1], t)]
+}
+
+fn slerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let a = Quaternion::new(a[3], a[0], a[1], a[2]);
+    let mut b = Quaternion::new(b[3], b[0], b[1], b[2]);
+
+    // q and -q are the same rotation; take the short way round
+    if a.dot(b) < 0.0 {
+        b = -b;
+    }
+
+    let q = a.slerp(b, t);
+    [q.v.x, q.v.y, q.v.z, q.s]
+}
+
+pub trait Source<T> {
+    fn value(&self, report: &TrackingReport) -> T;
+
+    /// the channel this reads, and the kind of value it expects to find there
+    fn channel(&self) -> (&str, FieldKind);
+}
+
+/// the name of a float channel
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct FloatSource(pub String);
+
+impl Source<f32> for FloatSource {
+    fn value(&self, report: &TrackingReport) -> f32 {
+        report
+            .floats
+            .get(&self.0)
+            .copied()
+            .unwrap_or_else(|| resting_float(&self.0))
+    }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        (&self.0, FieldKind::Float)
+    }
+}
+
+/// the name of a rotation channel
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct QuatSource(pub String);
+
 impl Source<Quaternion<f32>> for QuatSource {
     fn value(&self, report: &TrackingReport) -> Quaternion<f32> {
-        let value = match self {
-            QuatSource::HeadRotation => report.head_rotation,
-        };
+        let value = report
+            .rotations
+            .get(&self.0)
+            .copied()
+            .unwrap_or(REST_ROTATION);
 
         Quaternion {
             v: Vector3 {
@@ -97,28 +451,84 @@ impl Source<Quaternion<f32>> for QuatSource {
             s: value[3],
         }
     }
-}
 
-#[derive(Debug, Deserialize, Copy, Clone)]
-#[serde(rename_all = "snake_case")]
-pub enum Vec2Source {
-    FacePosition,
-    LeftGaze,
-    RightGaze,
-    Gaze,
+    fn channel(&self) -> (&str, FieldKind) {
+        (&self.0, FieldKind::Rotation)
+    }
 }
 
+/// the name of a 2d vector channel
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Vec2Source(pub String);
+
 impl Source<Vector2<f32>> for Vec2Source {
     fn value(&self, report: &TrackingReport) -> Vector2<f32> {
-        let v: Vector2<f32> = (match self {
-            Vec2Source::FacePosition => report.face_position,
-            Vec2Source::LeftGaze => report.left_gaze,
-            Vec2Source::RightGaze => report.right_gaze,
-            Vec2Source::Gaze => report.gaze,
-        })
-        .into();
+        let v: Vector2<f32> = report
+            .vec2s
+            .get(&self.0)
+            .copied()
+            .unwrap_or(REST_VEC2)
+            .into();
 
         // invert to behave like a mirror
         v.mul(-1.0)
     }
+
+    fn channel(&self) -> (&str, FieldKind) {
+        (&self.0, FieldKind::Vec2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// every standard channel, as the bundled tracker announces them
+    fn standard() -> HashMap<String, FieldKind> {
+        STANDARD_CHANNELS
+            .iter()
+            .map(|(name, kind)| (name.to_string(), *kind))
+            .collect()
+    }
+
+    #[test]
+    fn the_standard_channels_are_fine() {
+        assert_eq!(check_fields(&standard()), Ok(()));
+    }
+
+    #[test]
+    fn missing_channels_are_fine() {
+        let mut announced = standard();
+        announced.remove("mouth_wide");
+        announced.remove("gaze");
+        assert_eq!(check_fields(&announced), Ok(()));
+    }
+
+    #[test]
+    fn extra_channels_are_fine() {
+        let mut announced = standard();
+        announced.insert("tongue_out".to_string(), FieldKind::Float);
+        announced.insert("hand_rotation".to_string(), FieldKind::Rotation);
+        assert_eq!(check_fields(&announced), Ok(()));
+    }
+
+    #[test]
+    fn channels_of_the_wrong_kind_are_named() {
+        let mut announced = standard();
+        announced.insert("mouth_open".to_string(), FieldKind::Vec2);
+        announced.insert("head_rotation".to_string(), FieldKind::Float);
+
+        let message = check_fields(&announced).unwrap_err();
+        assert!(
+            message.contains("`mouth_open` should be a float, not a vec2"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("`head_rotation` should be a rotation, not a float"),
+            "{}",
+            message
+        );
+    }
 }
@@ -0,0 +1,258 @@
+//! running a tracker backend on its own thread, and restarting it when it falls over
+
+use super::{mailbox, ControlMessage, Interrupter, MailboxReceiver, MailboxSender};
+use super::{RunTrackerError, StampedReport, TrackerBackend};
+use log::{error, info, warn};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait between restarts of a tracker that keeps falling over, and when to give up
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    /// how long to wait before the first restart. this doubles with every restart that doesn't
+    /// get as far as a report, up to `max`
+    pub initial: Duration,
+    pub max: Duration,
+
+    /// how many restarts in a row can fail to produce a report before we give up
+    pub max_restarts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(30),
+            max_restarts: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackerState {
+    Starting,
+    Running,
+
+    /// the tracker fell over, and we're waiting to start it again
+    Restarting,
+
+    /// the tracker kept falling over, so we've stopped trying
+    Failed,
+
+    /// the tracker ran out of reports, or was told to stop
+    Stopped,
+}
+
+/// A tracker running on its own thread. Dropping this stops the tracker and waits for it to
+/// clean up after itself.
+pub struct Tracker {
+    pub reports: MailboxReceiver<StampedReport>,
+    control_tx: Sender<ControlMessage>,
+    interrupter: Arc<Mutex<Option<Interrupter>>>,
+    state: Arc<Mutex<TrackerState>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Tracker {
+    /// run `backend` on its own thread, restarting it according to `backoff` when it fails
+    pub fn spawn(backend: Box<dyn TrackerBackend>, backoff: Backoff) -> Tracker {
+        let (report_tx, report_rx) = mailbox();
+        let (control_tx, control_rx) = channel();
+        let interrupter = Arc::new(Mutex::new(None));
+        let state = Arc::new(Mutex::new(TrackerState::Starting));
+
+        let mut supervisor = Supervisor {
+            backend,
+            backoff,
+            report_tx,
+            control_rx,
+            interrupter: interrupter.clone(),
+            state: state.clone(),
+        };
+
+        Tracker {
+            reports: report_rx,
+            control_tx,
+            interrupter,
+            state,
+            thread: Some(thread::spawn(move || supervisor.run())),
+        }
+    }
+
+    pub fn send(&self, message: ControlMessage) {
+        if self.control_tx.send(message).is_err() {
+            warn!("tracker is not running");
+        }
+    }
+
+    /// a way to send messages to the tracker from elsewhere
+    pub fn control(&self) -> TrackerControl {
+        TrackerControl(self.control_tx.clone())
+    }
+
+    pub fn state(&self) -> TrackerState {
+        *self.state.lock().unwrap()
+    }
+}
+
+/// Sends messages to a tracker from somewhere other than the renderer, like an OBS hotkey
+#[derive(Clone)]
+pub struct TrackerControl(Sender<ControlMessage>);
+
+impl TrackerControl {
+    pub fn send(&self, message: ControlMessage) {
+        if self.0.send(message).is_err() {
+            warn!("tracker is not running");
+        }
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        // if this fails, the thread has already finished
+        let _ = self.control_tx.send(ControlMessage::Die);
+
+        if let Some(interrupt) = self.interrupter.lock().unwrap().as_ref() {
+            interrupt();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("tracker thread panicked");
+            }
+        }
+    }
+}
+
+enum Outcome {
+    Stopped,
+    Failed {
+        reported: bool,
+    },
+
+    /// restarting won't help
+    Incompatible,
+}
+
+/// the tracker thread's half of a `Tracker`
+struct Supervisor {
+    backend: Box<dyn TrackerBackend>,
+    backoff: Backoff,
+    report_tx: MailboxSender<StampedReport>,
+    control_rx: Receiver<ControlMessage>,
+    interrupter: Arc<Mutex<Option<Interrupter>>>,
+    state: Arc<Mutex<TrackerState>>,
+}
+
+impl Supervisor {
+    fn set_state(&self, state: TrackerState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    /// start the backend and pass on its reports until it stops
+    fn run_once(&mut self) -> Outcome {
+        if let Err(e) = self.backend.start() {
+            self.backend.shutdown();
+            return match e {
+                RunTrackerError::Incompatible(message) => {
+                    error!("incompatible tracker: {}", message);
+                    Outcome::Incompatible
+                }
+                e => {
+                    error!("could not start tracker: {:?}", e);
+                    Outcome::Failed { reported: false }
+                }
+            };
+        }
+        *self.interrupter.lock().unwrap() = self.backend.interrupter();
+        self.set_state(TrackerState::Running);
+
+        let mut reported = false;
+
+        let outcome = 'reports: loop {
+            while let Ok(cm) = self.control_rx.try_recv() {
+                let dying = matches!(cm, ControlMessage::Die);
+                self.backend.send(cm);
+                if dying {
+                    break 'reports Outcome::Stopped;
+                }
+            }
+
+            match self.backend.next_report() {
+                Some(report) => {
+                    reported = true;
+                    let stamped = StampedReport {
+                        received: Instant::now(),
+                        report,
+                    };
+                    if self.report_tx.send(stamped).is_err() {
+                        // nobody is rendering any more
+                        break Outcome::Stopped;
+                    }
+                }
+                None if self.backend.failed() => break Outcome::Failed { reported },
+                None => break Outcome::Stopped,
+            }
+        };
+
+        self.backend.shutdown();
+        outcome
+    }
+
+    /// wait for `duration`, unless we're told to stop first. returns whether to carry on
+    fn wait(&self, duration: Duration) -> bool {
+        let until = Instant::now() + duration;
+
+        loop {
+            let remaining = until.saturating_duration_since(Instant::now());
+            match self.control_rx.recv_timeout(remaining) {
+                Ok(ControlMessage::Die) | Err(RecvTimeoutError::Disconnected) => return false,
+                Ok(ControlMessage::Calibrate) => info!("can't calibrate while the tracker is down"),
+                Err(RecvTimeoutError::Timeout) => return true,
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        let mut backoff = self.backoff.initial;
+        let mut restarts = 0;
+
+        loop {
+            match self.run_once() {
+                Outcome::Stopped => {
+                    self.set_state(TrackerState::Stopped);
+                    return;
+                }
+                Outcome::Incompatible => {
+                    self.set_state(TrackerState::Failed);
+                    return;
+                }
+                Outcome::Failed { reported } => {
+                    if reported {
+                        backoff = self.backoff.initial;
+                        restarts = 0;
+                    }
+
+                    if restarts >= self.backoff.max_restarts {
+                        error!("tracker keeps failing; giving up");
+                        self.set_state(TrackerState::Failed);
+                        return;
+                    }
+
+                    warn!("tracker stopped; restarting in {:?}", backoff);
+                    self.set_state(TrackerState::Restarting);
+                    if !self.wait(backoff) {
+                        self.set_state(TrackerState::Stopped);
+                        return;
+                    }
+
+                    restarts += 1;
+                    backoff = (backoff * 2).min(self.backoff.max);
+                    self.set_state(TrackerState::Starting);
+                }
+            }
+        }
+    }
+}
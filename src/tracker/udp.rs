@@ -0,0 +1,101 @@
+//! listening for packets from trackers that run outside of layertuber
+
+use super::Interrupter;
+use log::{error, info};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A UDP socket on a port of our choosing, whose blocking reads can be interrupted from another
+/// thread
+pub struct UdpListener {
+    /// what we're listening to, for logs
+    name: &'static str,
+    port: u16,
+    socket: Option<UdpSocket>,
+    buf: Vec<u8>,
+
+    // shared with the interrupter, which wakes us with an empty packet
+    stopping: Arc<AtomicBool>,
+    failed: bool,
+}
+
+impl UdpListener {
+    pub fn new(name: &'static str, port: u16) -> UdpListener {
+        UdpListener {
+            name,
+            port,
+            socket: None,
+            buf: vec![0; 65536],
+            stopping: Arc::new(AtomicBool::new(false)),
+            failed: false,
+        }
+    }
+
+    /// start listening on every interface, so that the tracker can be on another machine
+    pub fn bind(&mut self) -> io::Result<()> {
+        self.stopping.store(false, Ordering::SeqCst);
+        self.failed = false;
+        self.socket = Some(UdpSocket::bind((Ipv4Addr::UNSPECIFIED, self.port))?);
+        info!("listening for {} on port {}", self.name, self.port);
+        Ok(())
+    }
+
+    /// block until the next packet arrives, or return `None` if we've been told to stop or
+    /// something went wrong
+    pub fn recv(&mut self) -> Option<&[u8]> {
+        loop {
+            let received = self.socket.as_ref()?.recv(&mut self.buf);
+
+            if self.stopping.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            match received {
+                Ok(len) => return Some(&self.buf[..len]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error!("could not receive from {}: {}", self.name, e);
+                    self.failed = true;
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// send a packet from the port we're listening on, for trackers that reply to whoever asks
+    pub fn send_to(&self, bytes: &[u8], addr: SocketAddr) -> io::Result<()> {
+        match &self.socket {
+            Some(socket) => socket.send_to(bytes, addr).map(|_| ()),
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "not listening")),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+
+    /// whether the last `None` from `recv` was because something went wrong
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn interrupter(&self) -> Interrupter {
+        let stopping = self.stopping.clone();
+        let name = self.name;
+        let wake = SocketAddr::from((Ipv4Addr::LOCALHOST, self.port));
+
+        Box::new(move || {
+            stopping.store(true, Ordering::SeqCst);
+            let sent = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).and_then(|s| s.send_to(&[], wake));
+            if let Err(e) = sent {
+                error!("could not wake {} listener: {}", name, e);
+            }
+        })
+    }
+
+    pub fn close(&mut self) {
+        self.socket = None;
+    }
+}
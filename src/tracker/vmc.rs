@@ -0,0 +1,166 @@
+//! receiving the Virtual Motion Capture protocol, OSC over UDP, from apps that send it
+
+use super::calibration::Calibration;
+use super::osc::{parse_packet, OscMessage};
+use super::report::FACE_FOUND;
+use super::udp::UdpListener;
+use super::{ControlMessage, Interrupter, RunTrackerError, TrackerBackend, TrackingReport};
+use log::error;
+use std::collections::HashMap;
+
+/// the bone whose rotation becomes `head_rotation`
+const HEAD_BONE: &str = "Head";
+
+/// Listens for VMC messages. Every blendshape becomes a float channel with the same name, and a
+/// report is made whenever the sender applies them
+pub struct VmcTracker {
+    listener: UdpListener,
+    blendshapes: HashMap<String, f32>,
+    head: Option<[f32; 4]>,
+
+    /// whether the sender last said it could see a face. senders that don't say are assumed to
+    tracking: bool,
+
+    calibration: Calibration,
+}
+
+impl VmcTracker {
+    pub fn new(port: u16, calibration: Calibration) -> VmcTracker {
+        VmcTracker {
+            listener: UdpListener::new("VMC", port),
+            blendshapes: HashMap::new(),
+            head: None,
+            tracking: true,
+            calibration,
+        }
+    }
+
+    /// take in a message, returning whether it's time to make a report
+    fn handle(&mut self, message: OscMessage) -> bool {
+        let args = &message.args;
+
+        match message.address.as_str() {
+            "/VMC/Ext/Blend/Val" => match (args.first().and_then(|a| a.as_str()), args.get(1)) {
+                (Some(name), Some(value)) => {
+                    if let Some(value) = value.as_f32() {
+                        self.blendshapes.insert(name.to_string(), value);
+                    }
+                }
+                _ => error!("got a bad blendshape from VMC: {:?}", args),
+            },
+            "/VMC/Ext/Bone/Pos" => {
+                let values: Vec<f32> = args.iter().skip(1).filter_map(|a| a.as_f32()).collect();
+                match (args.first().and_then(|a| a.as_str()), values.as_slice()) {
+                    (Some(HEAD_BONE), [_px, _py, _pz, x, y, z, w]) => {
+                        // VMC uses unity's left-handed, y-up space. flipping y gets us to the
+                        // right-handed, y-down space the bundled tracker reports in
+                        self.head = Some([-x, *y, -z, *w]);
+                    }
+                    (Some(_), _) => (),
+                    _ => error!("got a bad bone from VMC: {:?}", args),
+                }
+            }
+            "/VMC/Ext/OK" => {
+                // the fourth argument, if there is one, is 1 while the sender is tracking
+                if let Some(status) = args.get(3).and_then(|a| a.as_f32()) {
+                    self.tracking = status == 1.0;
+                }
+            }
+            "/VMC/Ext/Blend/Apply" => return true,
+            _ => (),
+        }
+
+        false
+    }
+
+    fn report(&self) -> TrackingReport {
+        // senders carry on sending the last values they had when they lose the face
+        if !self.tracking {
+            return TrackingReport::lost();
+        }
+
+        let mut report = TrackingReport::default();
+        let blendshape = |name: &str| self.blendshapes.get(name).copied();
+
+        report.floats.extend(self.blendshapes.clone());
+
+        // VRM's standard blendshapes, for rigs made for the bundled tracker. its blinks are how
+        // open the eyes are, where VRM's are how closed
+        let blink = match blendshape("Blink") {
+            Some(b) => 1.0 - b,
+            None => {
+                let left = blendshape("Blink_L").unwrap_or(0.0);
+                let right = blendshape("Blink_R").unwrap_or(0.0);
+                1.0 - (left + right) / 2.0
+            }
+        };
+
+        // a sender that only has `Blink` closes both eyes with it
+        let blink_left = blendshape("Blink_L").map_or(blink, |b| 1.0 - b);
+        let blink_right = blendshape("Blink_R").map_or(blink, |b| 1.0 - b);
+
+        let standard = [
+            ("blink", blink),
+            ("blink_left", blink_left),
+            ("blink_right", blink_right),
+            ("mouth_open", blendshape("A").unwrap_or(0.0)),
+            ("mouth_wide", blendshape("I").unwrap_or(0.0)),
+            (FACE_FOUND, 1.0),
+        ];
+        for (name, value) in standard {
+            report.floats.insert(name.to_string(), value);
+        }
+
+        if let Some(head) = self.head {
+            report.rotations.insert("head_rotation".to_string(), head);
+        }
+
+        report
+    }
+}
+
+impl TrackerBackend for VmcTracker {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        Ok(self.listener.bind()?)
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        loop {
+            let messages = match parse_packet(self.listener.recv()?) {
+                Ok(m) => m,
+                Err(e) => {
+                    error!("got a bad packet from VMC: {}", e);
+                    continue;
+                }
+            };
+
+            let mut applied = false;
+            for message in messages {
+                applied |= self.handle(message);
+            }
+
+            if applied {
+                return Some(self.calibration.apply(self.report()));
+            }
+        }
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        match message {
+            ControlMessage::Calibrate => self.calibration.reset(),
+            ControlMessage::Die => self.listener.stop(),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.listener.failed()
+    }
+
+    fn interrupter(&self) -> Option<Interrupter> {
+        Some(self.listener.interrupter())
+    }
+
+    fn shutdown(&mut self) {
+        self.listener.close();
+    }
+}
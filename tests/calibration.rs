@@ -0,0 +1,51 @@
+//! Checks that a tracker's neutral pose outlives the tracker.
+
+mod common;
+
+use common::close;
+use layertuber::tracker::{Calibration, TrackingReport};
+use std::{env, fs, process};
+
+fn report(mouth_open: f32, face_position: [f32; 2]) -> TrackingReport {
+    let mut report = TrackingReport::default();
+    report.floats.insert("mouth_open".to_string(), mouth_open);
+    report.floats.insert("blink".to_string(), 0.9);
+    report
+        .vec2s
+        .insert("face_position".to_string(), face_position);
+    report
+}
+
+#[test]
+fn neutral_pose_is_kept_between_runs() {
+    let dir = env::temp_dir().join(format!("layertuber-calibration-{}", process::id()));
+    let path = dir.join("calibration.json");
+
+    let mut calibration = Calibration::load_from(path.clone(), "pretend");
+    let neutral = calibration.apply(report(0.1, [0.5, 0.25]));
+    assert!(close(neutral.floats["mouth_open"], 0.0));
+    assert!(close(neutral.floats["blink"], 1.0));
+    assert_eq!(neutral.vec2s["face_position"], [0.0, 0.0]);
+    drop(calibration);
+
+    // the next run picks up where the last left off
+    let mut calibration = Calibration::load_from(path.clone(), "pretend");
+    let moved = calibration.apply(report(0.6, [0.75, 0.25]));
+    assert!(close(moved.floats["mouth_open"], 0.5));
+    assert!(close(moved.vec2s["face_position"][0], 0.25));
+
+    // other trackers have their own
+    let mut other = Calibration::load_from(path.clone(), "other");
+    let first = other.apply(report(0.6, [0.75, 0.25]));
+    assert!(close(first.floats["mouth_open"], 0.0));
+
+    // and resetting makes the next report the neutral one, for next time too
+    calibration.reset();
+    calibration.apply(report(0.6, [0.75, 0.25]));
+    let mut calibration = Calibration::load_from(path.clone(), "pretend");
+    let still = calibration.apply(report(0.6, [0.75, 0.25]));
+    assert!(close(still.floats["mouth_open"], 0.0));
+    assert!(close(still.vec2s["face_position"][0], 0.0));
+
+    fs::remove_dir_all(dir).unwrap();
+}
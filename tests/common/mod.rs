@@ -0,0 +1,53 @@
+//! Helpers shared between the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+pub fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+/// a port on loopback that nothing was listening on a moment ago
+pub fn free_port() -> u16 {
+    UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// A copy of the stick figure in a temporary directory of its own, with a config of our choosing,
+/// removed again when dropped
+pub struct StickFigure {
+    pub dir: PathBuf,
+    pub ora_path: PathBuf,
+}
+
+impl StickFigure {
+    pub fn with_config(name: &str, config: &str) -> StickFigure {
+        let dir = env::temp_dir().join(format!("layertuber-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let ora_path = dir.join("puppet.ora");
+        fs::copy(puppet_path("stick figure"), &ora_path).unwrap();
+        fs::write(dir.join("puppet.ora.layertuber.yaml"), config).unwrap();
+
+        StickFigure { dir, ora_path }
+    }
+}
+
+impl Drop for StickFigure {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// the .ora file of one of the bundled puppets
+pub fn puppet_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("puppets")
+        .join(name)
+        .join(format!("{}.ora", name))
+}
@@ -0,0 +1,187 @@
+//! Loads the stick figure with broken configs, and checks that the problems are pointed out
+//! where they are.
+
+mod common;
+
+use common::StickFigure;
+use layertuber::puppet::{Rig, RigError};
+
+/// every problem with `config` as the stick figure's config, as `(position, message)`
+fn problems(name: &str, config: &str) -> Vec<(Option<(usize, usize)>, String)> {
+    let stick_figure = StickFigure::with_config(&format!("diagnostics-{}", name), config);
+    let result = Rig::open(&stick_figure.ora_path);
+
+    match result {
+        Err(RigError::Config(diagnostics)) => diagnostics
+            .diagnostics
+            .into_iter()
+            .map(|d| (d.position, d.message))
+            .collect(),
+        Err(e) => panic!("expected problems with the config, got {}", e),
+        Ok(_) => panic!("expected problems with the config, but it loaded"),
+    }
+}
+
+#[test]
+fn misspelt_options_get_a_suggestion() {
+    let problems = problems(
+        "suggestion",
+        "layers:\n  head:\n    rotate3d:\n      source: head_rotation\n",
+    );
+
+    assert_eq!(problems.len(), 1);
+    let (position, message) = &problems[0];
+    assert_eq!(*position, Some((3, 5)));
+    assert!(message.contains("unknown field `rotate3d`"), "{}", message);
+    assert!(
+        message.ends_with("(did you mean `rotate_3d`?)"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn unknown_layers_are_pointed_at() {
+    let problems = problems(
+        "layers",
+        concat!(
+            "layers:\n",
+            "  # bodd: isn't here yet\n",
+            "  head:\n",
+            "    visible: true\n",
+            "# not the end of the layers\n",
+            "  bodd:\n",
+            "    visible: false\n",
+            "  \"hat\":\n",
+            "    visible: false\n",
+            "  'scarf': {visible: false}\n",
+        ),
+    );
+
+    assert_eq!(
+        problems,
+        [
+            (
+                Some((6, 3)),
+                "there is no layer or stack called `bodd` in the rig".to_string()
+            ),
+            (
+                Some((8, 3)),
+                "there is no layer or stack called `hat` in the rig".to_string()
+            ),
+            (
+                Some((10, 3)),
+                "there is no layer or stack called `scarf` in the rig".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn channels_in_comments_are_not_pointed_at() {
+    let problems = problems(
+        "comments",
+        concat!(
+            "# mouht_open is a typo, and this comment shouldn't be blamed for it\n",
+            "layers:\n",
+            "  open mouth:\n",
+            "    visible_when:\n",
+            "      source: mouht_open # mouht_open\n",
+            "      greater_than: 0.1\n",
+        ),
+    );
+
+    assert_eq!(
+        problems,
+        [(
+            Some((5, 15)),
+            "there is no channel called `mouht_open` (did you mean `mouth_open`?)".to_string()
+        )]
+    );
+}
+
+#[test]
+fn tracking_loss_timings_must_be_waitable() {
+    let problems = problems(
+        "timings",
+        "tracking_lost:\n  after: .inf\n  ease: -1\nlayers: {}\n",
+    );
+
+    assert_eq!(
+        problems,
+        [
+            (
+                Some((2, 3)),
+                "`tracking_lost.after` should be a number of seconds, not inf".to_string()
+            ),
+            (
+                Some((3, 3)),
+                "`tracking_lost.ease` should be a number of seconds, not -1".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn tracking_loss_timings_are_found_in_tracking_lost() {
+    // `ease` and `after` turn up before `tracking_lost` does, but aren't what's wrong
+    let block = problems(
+        "timings-elsewhere",
+        concat!(
+            "channels:\n",
+            "  floats: [ease, after]\n",
+            "tracking_lost:\n",
+            "  pose: hold\n",
+            "  ease: -1\n",
+            "  after: .nan\n",
+            "layers: {}\n",
+        ),
+    );
+    let positions: Vec<_> = block.iter().map(|(position, _)| *position).collect();
+    assert_eq!(positions, [Some((6, 3)), Some((5, 3))]);
+
+    let flow = problems(
+        "timings-flow",
+        "channels: {floats: [ease]}\ntracking_lost: {pose: hold, ease: -1}\nlayers: {}\n",
+    );
+    assert_eq!(
+        flow,
+        [(
+            Some((2, 29)),
+            "`tracking_lost.ease` should be a number of seconds, not -1".to_string()
+        )]
+    );
+}
+
+#[test]
+fn misspelt_axes_get_a_suggestion() {
+    let problems = problems(
+        "scale",
+        concat!(
+            "layers:\n",
+            "  head:\n",
+            "    follow:\n",
+            "      source: face_position\n",
+            "      scale: {x: 1, yy: 2}\n",
+        ),
+    );
+
+    assert_eq!(problems.len(), 1);
+    let (position, message) = &problems[0];
+    assert_eq!(*position, Some((5, 21)));
+    assert!(message.contains("unknown field `yy`"), "{}", message);
+    assert!(message.ends_with("(did you mean `y`?)"), "{}", message);
+}
+
+#[test]
+fn scales_can_be_one_number_or_one_per_axis() {
+    for scale in ["2", "-0.5", "{x: 1, y: -2}"] {
+        let config = format!(
+            "layers:\n  head:\n    follow:\n      source: face_position\n      scale: {}\n",
+            scale
+        );
+        let stick_figure = StickFigure::with_config("diagnostics-scales", &config);
+        let opened = Rig::open(&stick_figure.ora_path);
+        assert!(opened.is_ok(), "{}: {:?}", scale, opened.err());
+    }
+}
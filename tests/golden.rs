@@ -0,0 +1,395 @@
+//! Renders the bundled puppets with some synthetic tracking reports and compares the results
+//! against the reference images in tests/golden/.
+//!
+//! A missing reference image is a failure. After an intentional change to how puppets render, run
+//! with `LAYERTUBER_BLESS=1` to write new references, and commit them. Rendering needs a display
+//! to create an OpenGL context on, so without one these tests fail, unless
+//! `LAYERTUBER_SKIP_GOLDEN=1` says to skip them.
+//!
+//! This runs without the standard test harness because the graphics context has to be created on
+//! the main thread.
+
+mod common;
+
+use common::{puppet_path, StickFigure};
+use layertuber::puppet::offline::{self, render_frame, write_png};
+use layertuber::puppet::{Puppet, Rig};
+use layertuber::tracker::{TimedReport, TrackerState, TrackingReport};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, exit};
+use three_d::{Context, HeadlessContext};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+/// how far any channel of any pixel may drift from the reference before we complain
+const TOLERANCE: u8 = 8;
+
+fn floats(values: &[(&str, f32)]) -> HashMap<String, f32> {
+    values.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+}
+
+fn vec2s(values: &[(&str, [f32; 2])]) -> HashMap<String, [f32; 2]> {
+    values.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+}
+
+fn poses() -> Vec<(&'static str, TrackingReport)> {
+    vec![
+        ("neutral", TrackingReport::default()),
+        (
+            "blink and open mouth",
+            TrackingReport {
+                // blinks are how open the eyes are
+                floats: floats(&[
+                    ("blink", 0.0),
+                    ("blink_left", 0.0),
+                    ("blink_right", 0.0),
+                    ("mouth_open", 0.5),
+                    ("mouth_wide", 0.5),
+                ]),
+                ..TrackingReport::default()
+            },
+        ),
+        (
+            "turn head",
+            TrackingReport {
+                // about 20 degrees of yaw and 10 of pitch
+                rotations: HashMap::from([(
+                    "head_rotation".to_string(),
+                    [0.0868, 0.1710, 0.0, 0.9815],
+                )]),
+                ..TrackingReport::default()
+            },
+        ),
+        (
+            "move and look around",
+            TrackingReport {
+                floats: floats(&[
+                    ("eyebrow_updown", 0.5),
+                    ("eyebrow_updown_left", 0.5),
+                    ("eyebrow_updown_right", 0.5),
+                ]),
+                vec2s: vec2s(&[
+                    ("face_position", [0.2, -0.1]),
+                    ("left_gaze", [0.1, 0.05]),
+                    ("right_gaze", [0.1, 0.05]),
+                    ("gaze", [0.1, 0.05]),
+                ]),
+                ..TrackingReport::default()
+            },
+        ),
+    ]
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn read_png(path: &Path) -> (u32, u32, Vec<u8>) {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    assert_eq!(
+        (info.color_type, info.bit_depth),
+        (png::ColorType::Rgba, png::BitDepth::Eight),
+        "{} should be an 8-bit RGBA image",
+        path.display()
+    );
+    buf.truncate(info.buffer_size());
+    (info.width, info.height, buf)
+}
+
+/// compare `pixels` against the reference image called `name`, or replace the reference if we've
+/// been asked to. returns what's wrong, if anything
+fn check(name: &str, pixels: &[[u8; 4]]) -> Option<String> {
+    let reference_path = golden_dir().join(format!("{}.png", name));
+
+    if env::var_os("LAYERTUBER_BLESS").is_some() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        write_png(&reference_path, WIDTH, HEIGHT, pixels).unwrap();
+        eprintln!("wrote new reference {}", reference_path.display());
+        return None;
+    }
+
+    if !reference_path.exists() {
+        return Some(format!(
+            "{}: there is no reference at {}; run with LAYERTUBER_BLESS=1 to write one",
+            name,
+            reference_path.display()
+        ));
+    }
+
+    let (width, height, reference) = read_png(&reference_path);
+    if (width, height) != (WIDTH, HEIGHT) {
+        return Some(format!("{}: reference is {}x{}", name, width, height));
+    }
+
+    let differing = pixels
+        .concat()
+        .chunks(4)
+        .zip(reference.chunks(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(b.iter())
+                .any(|(a, b)| a.abs_diff(*b) > TOLERANCE)
+        })
+        .count();
+
+    if differing > 0 {
+        let actual_path = env::temp_dir().join(format!("{}.actual.png", name));
+        write_png(&actual_path, WIDTH, HEIGHT, pixels).unwrap();
+        return Some(format!(
+            "{}: {} pixels differ from the reference (see {})",
+            name,
+            differing,
+            actual_path.display()
+        ));
+    }
+
+    eprintln!("{}: ok", name);
+    None
+}
+
+fn check_puppet(context: &Context, puppet_name: &str, ora_path: &Path) -> Vec<String> {
+    let rig = Rig::open(ora_path).unwrap();
+    let mut puppet = Puppet::new(context, &rig);
+
+    poses()
+        .into_iter()
+        .filter_map(|(pose_name, report)| {
+            let pixels = render_frame(
+                context,
+                &mut puppet,
+                WIDTH,
+                HEIGHT,
+                &report,
+                TrackerState::Running,
+            );
+            check(&format!("{} - {}", puppet_name, pose_name), &pixels)
+        })
+        .collect()
+}
+
+/// the stick figure at rest, drawn while the tracker isn't working
+fn check_tracker_states(context: &Context) -> Vec<String> {
+    let rig = Rig::open(&puppet_path("stick figure")).unwrap();
+    let mut puppet = Puppet::new(context, &rig);
+
+    [
+        ("restarting", TrackerState::Restarting),
+        ("failed", TrackerState::Failed),
+    ]
+    .into_iter()
+    .filter_map(|(state_name, state)| {
+        let report = TrackingReport::default();
+        let pixels = render_frame(context, &mut puppet, WIDTH, HEIGHT, &report, state);
+        check(&format!("stick figure - tracker {}", state_name), &pixels)
+    })
+    .collect()
+}
+
+/// `color` at each pixel of a `width` by `height` image, encoded as a PNG
+fn png_data(width: u32, height: u32, color: impl Fn(u32, u32) -> [u8; 4]) -> Vec<u8> {
+    let pixels: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| color(x, y))
+        .collect();
+
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&pixels)
+        .unwrap();
+    data
+}
+
+/// write a rig to `dir` with a background that's blue on the left and orange on the right, a
+/// multiply layer across the top, and a screen layer across the bottom. each layer is opaque in its
+/// top half and half transparent in its bottom half
+fn write_composite_ops_rig(dir: &Path) -> PathBuf {
+    let stack = r#"<?xml version="1.0" encoding="UTF-8"?>
+<image version="0.0.3" w="64" h="48">
+  <layer name="screen" src="data/screen.png" x="8" y="26" composite-op="svg:screen"/>
+  <layer name="multiply" src="data/multiply.png" x="8" y="6" composite-op="svg:multiply"/>
+  <layer name="background" src="data/background.png" x="0" y="0"/>
+</image>"#;
+
+    let strip = |color: [u8; 3]| {
+        png_data(48, 16, move |_, y| {
+            [color[0], color[1], color[2], if y < 8 { 255 } else { 128 }]
+        })
+    };
+    let images = [
+        (
+            "data/background.png",
+            png_data(64, 48, |x, _| {
+                if x < 32 {
+                    [64, 128, 192, 255]
+                } else {
+                    [224, 160, 64, 255]
+                }
+            }),
+        ),
+        ("data/multiply.png", strip([255, 128, 128])),
+        ("data/screen.png", strip([64, 64, 160])),
+    ];
+
+    fs::create_dir_all(dir).unwrap();
+    let ora_path = dir.join("composite ops.ora");
+    let mut ora = ZipWriter::new(File::create(&ora_path).unwrap());
+
+    // OpenRaster wants the mimetype first, and uncompressed
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    ora.start_file("mimetype", stored).unwrap();
+    ora.write_all(b"image/openraster").unwrap();
+    ora.start_file("stack.xml", FileOptions::default()).unwrap();
+    ora.write_all(stack.as_bytes()).unwrap();
+    for (name, data) in images {
+        ora.start_file(name, FileOptions::default()).unwrap();
+        ora.write_all(&data).unwrap();
+    }
+    ora.finish().unwrap();
+
+    fs::write(
+        dir.join("composite ops.ora.layertuber.yaml"),
+        "layers: {}\n",
+    )
+    .unwrap();
+    ora_path
+}
+
+fn check_composite_ops(context: &Context) -> Vec<String> {
+    let dir = env::temp_dir().join(format!("layertuber-golden-composite-{}", process::id()));
+    let rig = Rig::open(&write_composite_ops_rig(&dir)).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut puppet = Puppet::new(context, &rig);
+    let report = TrackingReport::default();
+    let pixels = render_frame(
+        context,
+        &mut puppet,
+        WIDTH,
+        HEIGHT,
+        &report,
+        TrackerState::Running,
+    );
+    check("composite ops - multiply and screen", &pixels)
+        .into_iter()
+        .collect()
+}
+
+/// half a second of a turned head with an open mouth, then a second without a face, a report
+/// every frame at 30 frames per second
+fn losing_the_face() -> Vec<TimedReport> {
+    let face = TrackingReport {
+        floats: floats(&[("mouth_open", 1.0)]),
+        rotations: HashMap::from([("head_rotation".to_string(), [0.0, 0.2588, 0.0, 0.9659])]),
+        ..TrackingReport::default()
+    };
+
+    (0..=45)
+        .map(|frame| TimedReport {
+            time: frame as f64 / 30.0,
+            report: match frame {
+                0..=15 => face.clone(),
+                _ => TrackingReport::lost(),
+            },
+        })
+        .collect()
+}
+
+/// replay `losing_the_face` on the stick figure with `tracking_lost` as its tracking loss config,
+/// checking each of `frames`
+fn check_lost_face(
+    context: &Context,
+    name: &str,
+    tracking_lost: &str,
+    frames: &[(&str, usize)],
+) -> Vec<String> {
+    let config = fs::read_to_string(
+        puppet_path("stick figure").with_file_name("stick figure.ora.layertuber.yaml"),
+    )
+    .unwrap();
+    let stick_figure = StickFigure::with_config(
+        &format!("golden-{}", name.replace(' ', "-")),
+        &format!("{}\ntracking_lost:\n{}", config, tracking_lost),
+    );
+    let rig = Rig::open(&stick_figure.ora_path).unwrap();
+    let mut puppet = Puppet::new(context, &rig);
+
+    let reports = losing_the_face();
+    let poses: Vec<TrackingReport> = offline::poses(&rig, &reports, 30).collect();
+
+    frames
+        .iter()
+        .filter_map(|(frame_name, index)| {
+            let pixels = render_frame(
+                context,
+                &mut puppet,
+                WIDTH,
+                HEIGHT,
+                &poses[*index],
+                TrackerState::Running,
+            );
+            check(&format!("stick figure - {}, {}", name, frame_name), &pixels)
+        })
+        .collect()
+}
+
+fn main() {
+    if env::var_os("DISPLAY").is_none() && env::var_os("WAYLAND_DISPLAY").is_none() {
+        if env::var_os("LAYERTUBER_SKIP_GOLDEN").is_some() {
+            eprintln!("no display to create a graphics context on; skipping golden image tests");
+            return;
+        }
+
+        eprintln!(
+            "no display to create a graphics context on, so golden images can't be checked. \
+             set LAYERTUBER_SKIP_GOLDEN=1 to skip them"
+        );
+        exit(1);
+    }
+
+    let context = HeadlessContext::new().unwrap();
+
+    // the face is last seen half a second in, and counts as lost half a second after that. the
+    // puppet then takes 0.4 seconds to settle
+    let failures = [
+        check_puppet(&context, "demo", &puppet_path("demo")),
+        check_puppet(&context, "stick figure", &puppet_path("stick figure")),
+        check_tracker_states(&context),
+        check_composite_ops(&context),
+        check_lost_face(
+            &context,
+            "lost face",
+            "  pose: neutral\n  after: 0.5\n  ease: 0.4\n",
+            &[("easing", 36), ("settled", 45)],
+        ),
+        check_lost_face(
+            &context,
+            "lost face with a sign",
+            "  show: [head outline]\n",
+            &[("settled", 45)],
+        ),
+    ]
+    .concat();
+
+    if !failures.is_empty() {
+        eprintln!("{}", failures.join("\n"));
+        exit(1);
+    }
+}
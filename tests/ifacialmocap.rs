@@ -0,0 +1,54 @@
+//! Pretends to be a phone running iFacialMocap, and checks what the iFacialMocap backend makes of
+//! what it sends.
+
+mod common;
+
+use common::{close, free_port};
+use layertuber::tracker::{Calibration, IFacialMocapTracker, TrackerBackend};
+use std::net::{Ipv4Addr, UdpSocket};
+
+#[test]
+fn asks_for_tracking_and_maps_blendshapes() {
+    let phone = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = free_port();
+
+    let mut tracker =
+        IFacialMocapTracker::new(phone.local_addr().unwrap(), port, Calibration::new());
+    tracker.start().unwrap();
+
+    let mut buf = [0; 256];
+    let (len, from) = phone.recv_from(&mut buf).unwrap();
+    assert!(buf[..len].starts_with(b"iFacialMocap_"));
+    assert_eq!(from.port(), port);
+
+    let send = |text: &str| {
+        phone
+            .send_to(text.as_bytes(), (Ipv4Addr::LOCALHOST, port))
+            .unwrap();
+    };
+
+    send("eyeBlink_L-0|eyeBlink_R-0|jawOpen-10|=head#0,0,0,0.01,0.02,-0.5|rightEye#1,2,3|leftEye#1,2,3|");
+    let neutral = tracker.next_report().unwrap();
+    assert_eq!(neutral.floats["blink"], 1.0);
+    assert_eq!(neutral.floats["jawOpen"], 0.0);
+    assert_eq!(neutral.floats["mouth_open"], 0.0);
+
+    // the newer separator, and a head turned about 20 degrees and moved right 5cm
+    send("eyeBlink_L&100|eyeBlink_R&40|jawOpen&60|mouthSmile_R&25|=head#0,20,0,0.06,0.02,-0.5|");
+    let report = tracker.next_report().unwrap();
+    assert_eq!(report.floats["blink_left"], 0.0);
+    assert!(close(report.floats["blink_right"], 0.6));
+    assert!(close(report.floats["blink"], 0.3));
+    assert!(close(report.floats["mouth_open"], 0.5));
+    assert_eq!(report.floats["mouthSmileRight"], 0.25);
+    assert!(close(report.vec2s["face_position"][0], 0.05));
+    assert!(close(report.vec2s["face_position"][1], 0.0));
+    assert!(close(report.rotations["head_rotation"][1], 0.1736));
+
+    // garbage is skipped
+    send("=head#1,2|");
+    send("jawOpen-20|");
+    assert!(close(tracker.next_report().unwrap().floats["jawOpen"], 0.1));
+
+    tracker.shutdown();
+}
@@ -0,0 +1,45 @@
+//! Checks that a mailbox only ever holds the latest value, and never keeps the sender waiting.
+
+use layertuber::tracker::mailbox;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn unread_values_are_overwritten() {
+    let (sender, receiver) = mailbox();
+    assert_eq!(receiver.latest(), None);
+
+    sender.send(1).unwrap();
+    sender.send(2).unwrap();
+    sender.send(3).unwrap();
+    assert_eq!(receiver.latest(), Some(3));
+
+    // and each value is only read once
+    assert_eq!(receiver.latest(), None);
+    sender.send(4).unwrap();
+    assert_eq!(receiver.latest(), Some(4));
+}
+
+#[test]
+fn sending_never_waits_for_the_receiver() {
+    let (sender, receiver) = mailbox();
+
+    // a receiver that never gets round to reading
+    let sending = thread::spawn(move || {
+        let started = Instant::now();
+        for i in 0..100_000 {
+            sender.send(i).unwrap();
+        }
+        started.elapsed()
+    });
+
+    assert!(sending.join().unwrap() < Duration::from_secs(5));
+    assert_eq!(receiver.latest(), Some(99_999));
+}
+
+#[test]
+fn sending_fails_once_the_receiver_is_gone() {
+    let (sender, receiver) = mailbox();
+    drop(receiver);
+    assert_eq!(sender.send("hello"), Err("hello"));
+}
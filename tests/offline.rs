@@ -0,0 +1,60 @@
+//! Checks that puppets posed from a recording go through the same smoothing as live ones, at the
+//! times the reports arrived.
+
+mod common;
+
+use common::{close, StickFigure};
+use layertuber::puppet::offline::poses;
+use layertuber::puppet::Rig;
+use layertuber::tracker::{TimedReport, TrackingReport};
+
+fn mouth(time: f64, mouth_open: f32) -> TimedReport {
+    let mut report = TrackingReport::default();
+    report.floats.insert("mouth_open".to_string(), mouth_open);
+    TimedReport { time, report }
+}
+
+fn mouths(rig: &Rig, reports: &[TimedReport], fps: u32) -> Vec<f32> {
+    poses(rig, reports, fps)
+        .map(|report| report.floats["mouth_open"])
+        .collect()
+}
+
+const SMOOTHED: &str = "\
+filters:
+  mouth_open: {type: ema, alpha: 0.5}
+interpolation:
+  enabled: false
+tracking_lost:
+  ease: 0
+layers: {}
+";
+
+#[test]
+fn recordings_are_filtered() {
+    let stick_figure = StickFigure::with_config("offline-filtered", SMOOTHED);
+    let rig = Rig::open(&stick_figure.ora_path).unwrap();
+
+    // captured tracker output has no times, so each report gets a frame of its own
+    let reports = [mouth(0.0, 0.0), mouth(0.0, 1.0), mouth(0.0, 1.0)];
+    let mouths = mouths(&rig, &reports, 30);
+    assert_eq!(mouths.len(), 3);
+    assert!(close(mouths[0], 0.0), "{:?}", mouths);
+    assert!(close(mouths[1], 0.5), "{:?}", mouths);
+    assert!(close(mouths[2], 0.75), "{:?}", mouths);
+}
+
+#[test]
+fn frames_follow_report_times() {
+    let stick_figure = StickFigure::with_config("offline-timed", SMOOTHED);
+    let rig = Rig::open(&stick_figure.ora_path).unwrap();
+
+    // a frame every 0.1 seconds sees the first report alone, then nothing new, then only the
+    // latest of the two that arrived since
+    let reports = [mouth(0.0, 1.0), mouth(0.15, 0.0), mouth(0.18, 1.0)];
+    let mouths = mouths(&rig, &reports, 10);
+    assert_eq!(mouths.len(), 3);
+    assert!(close(mouths[0], 1.0), "{:?}", mouths);
+    assert!(close(mouths[1], 1.0), "{:?}", mouths);
+    assert!(close(mouths[2], 1.0), "{:?}", mouths);
+}
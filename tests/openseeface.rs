@@ -0,0 +1,146 @@
+//! Sends OpenSeeFace packets to the OpenSeeFace backend over loopback, and checks what it makes
+//! of them.
+
+mod common;
+
+use common::{close, free_port};
+use layertuber::tracker::{Calibration, ControlMessage, OpenSeeFaceTracker, TrackerBackend};
+use std::net::{Ipv4Addr, UdpSocket};
+
+/// a packet for one 640x480 face, laid out like OpenSeeFace's facetracker.py does it
+fn packet(quaternion: [f32; 4], face_at: [f32; 2], mouth_open: f32) -> Vec<u8> {
+    let mut packet = Vec::new();
+    let floats = |packet: &mut Vec<u8>, values: &[f32]| {
+        for v in values {
+            packet.extend_from_slice(&v.to_le_bytes());
+        }
+    };
+
+    packet.extend_from_slice(&1.5f64.to_le_bytes()); // time
+    packet.extend_from_slice(&0i32.to_le_bytes()); // id
+    floats(&mut packet, &[640.0, 480.0]);
+    floats(&mut packet, &[0.9, 0.7]); // eye blink
+    packet.push(1); // success
+    floats(&mut packet, &[0.0]); // pnp error
+    floats(&mut packet, &quaternion);
+    floats(&mut packet, &[0.0; 3 + 3]); // euler angles and translation
+    floats(&mut packet, &[1.0; 68]); // landmark confidence
+    for i in 0..68 {
+        // every face landmark in one spot, the pupils either side of it
+        let offset = match i {
+            66 => -10.0,
+            67 => 10.0,
+            _ => 0.0,
+        };
+        floats(&mut packet, &[face_at[0] + offset, face_at[1]]);
+    }
+    floats(&mut packet, &[0.0; 70 * 3]);
+
+    let mut features = [0.0; 14];
+    features[3] = 0.2; // eyebrow_updown_l
+    features[6] = 0.4; // eyebrow_updown_r
+    features[12] = mouth_open;
+    floats(&mut packet, &features);
+
+    packet
+}
+
+fn close2(a: [f32; 2], b: [f32; 2]) -> bool {
+    close(a[0], b[0]) && close(a[1], b[1])
+}
+
+#[test]
+fn derives_channels_relative_to_the_first_face() {
+    let port = free_port();
+    let mut tracker = OpenSeeFaceTracker::new(port, Calibration::new());
+    tracker.start().unwrap();
+
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let send = |packet: Vec<u8>| {
+        sender
+            .send_to(&packet, (Ipv4Addr::LOCALHOST, port))
+            .unwrap();
+    };
+
+    // about 20 degrees of yaw
+    let turned = [0.0, 0.1736, 0.0, 0.9848];
+
+    send(packet([0.0, 0.0, 0.0, 1.0], [320.0, 240.0], 0.1));
+    let neutral = tracker.next_report().unwrap();
+    assert_eq!(neutral.floats["mouth_open"], 0.0);
+    assert_eq!(neutral.floats["blink"], 1.0);
+    assert!(close2(neutral.vec2s["face_position"], [0.0, 0.0]));
+
+    send(packet(turned, [480.0, 240.0], 0.6));
+    let report = tracker.next_report().unwrap();
+    assert!(close(report.floats["mouth_open"], 0.5));
+    assert!(close(report.floats["blink_left"], 1.0));
+    assert!(close(report.floats["blink_right"], 1.0));
+    assert!(close(report.floats["eyebrow_updown"], 0.0));
+    assert!(close2(report.vec2s["face_position"], [0.25, 0.0]));
+    assert!(close(report.vec2s["gaze"][0], 0.25));
+
+    let rotation = report.rotations["head_rotation"];
+    for (a, b) in rotation.iter().zip(turned) {
+        assert!(close(*a, b), "{:?} should be {:?}", rotation, turned);
+    }
+
+    // calibrating makes the next face the neutral one
+    tracker.send(ControlMessage::Calibrate);
+    send(packet(turned, [480.0, 240.0], 0.6));
+    let report = tracker.next_report().unwrap();
+    assert!(close2(report.vec2s["face_position"], [0.0, 0.0]));
+    assert!(close(report.rotations["head_rotation"][1], 0.0));
+
+    // bad packets are skipped
+    send(vec![0; 12]);
+    send(packet(turned, [320.0, 240.0], 0.3));
+    assert!(close(
+        tracker.next_report().unwrap().floats["mouth_open"],
+        -0.3
+    ));
+
+    tracker.shutdown();
+}
+
+#[test]
+fn failed_fits_lose_the_face() {
+    let port = free_port();
+    let mut tracker = OpenSeeFaceTracker::new(port, Calibration::new());
+    tracker.start().unwrap();
+
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let send = |packet: Vec<u8>| {
+        sender
+            .send_to(&packet, (Ipv4Addr::LOCALHOST, port))
+            .unwrap();
+    };
+
+    let mut failed = packet([0.0, 0.0, 0.0, 1.0], [320.0, 240.0], 0.6);
+    failed[28] = 0; // success
+    send(failed);
+    assert!(!tracker.next_report().unwrap().face_found());
+
+    // a lost face doesn't become the neutral one
+    send(packet([0.0, 0.0, 0.0, 1.0], [320.0, 240.0], 0.1));
+    let report = tracker.next_report().unwrap();
+    assert!(report.face_found());
+    assert_eq!(report.floats["mouth_open"], 0.0);
+
+    tracker.shutdown();
+}
+
+#[test]
+fn interrupting_stops_a_blocked_listener() {
+    let mut tracker = OpenSeeFaceTracker::new(free_port(), Calibration::new());
+    tracker.start().unwrap();
+
+    let interrupt = tracker.interrupter().unwrap();
+    let waiting = std::thread::spawn(move || {
+        let report = tracker.next_report();
+        (report.is_none(), tracker.failed())
+    });
+
+    interrupt();
+    assert_eq!(waiting.join().unwrap(), (true, false));
+}
@@ -0,0 +1,134 @@
+//! Checks that recordings read back the way they were written.
+
+use layertuber::tracker::{
+    read_recording, RecordingTracker, ReplayTracker, TrackerBackend, TrackingReport,
+};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("layertuber-{}-{}.jsonl", name, process::id()))
+}
+
+#[test]
+fn captured_tracker_output_skips_the_hello() {
+    let path = temp_path("captured");
+    fs::write(
+        &path,
+        concat!(
+            r#"{"hello": {"protocol": 4, "fields": {"mouth_open": "float"}}}"#,
+            "\n",
+            r#"{"mouth_open": 0.25}"#,
+            "\n",
+            r#"{"mouth_open": 0.5}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+
+    let reports = read_recording(&path).unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].report.floats["mouth_open"], 0.25);
+
+    let mut replay = ReplayTracker::new(path.clone(), 0.0);
+    replay.start().unwrap();
+    assert_eq!(replay.next_report().unwrap().floats["mouth_open"], 0.25);
+    assert_eq!(replay.next_report().unwrap().floats["mouth_open"], 0.5);
+    assert!(replay.next_report().is_none());
+    replay.shutdown();
+
+    fs::remove_file(path).unwrap();
+}
+
+fn report(mouth_open: f32, face_position: [f32; 2]) -> TrackingReport {
+    let mut report = TrackingReport::default();
+    report.floats.insert("mouth_open".to_string(), mouth_open);
+    report
+        .vec2s
+        .insert("face_position".to_string(), face_position);
+    report
+        .rotations
+        .insert("head_rotation".to_string(), [0.0, 0.0, 0.6, 0.8]);
+    report
+}
+
+#[test]
+fn recordings_replay_what_was_recorded() {
+    let source = temp_path("source");
+    let recorded = temp_path("recorded");
+    let lines: Vec<String> = [report(0.25, [0.5, 0.0]), report(0.75, [0.0, -0.5])]
+        .iter()
+        .map(|r| serde_json::to_string(r).unwrap() + "\n")
+        .collect();
+    fs::write(&source, lines.concat()).unwrap();
+
+    let mut recording = RecordingTracker::new(
+        Box::new(ReplayTracker::new(source.clone(), 0.0)),
+        recorded.clone(),
+    );
+    recording.start().unwrap();
+    let passed_on: Vec<TrackingReport> = std::iter::from_fn(|| recording.next_report()).collect();
+    recording.shutdown();
+
+    let read: Vec<TrackingReport> = read_recording(&recorded)
+        .unwrap()
+        .into_iter()
+        .map(|timed| timed.report)
+        .collect();
+    assert_eq!(read, passed_on);
+    assert_eq!(read, [report(0.25, [0.5, 0.0]), report(0.75, [0.0, -0.5])]);
+
+    let mut replay = ReplayTracker::new(recorded.clone(), 1.0);
+    replay.start().unwrap();
+    let replayed: Vec<TrackingReport> = std::iter::from_fn(|| replay.next_report()).collect();
+    replay.shutdown();
+    assert_eq!(replayed, read);
+
+    fs::remove_file(source).unwrap();
+    fs::remove_file(recorded).unwrap();
+}
+
+#[test]
+fn reports_from_before_the_start_are_due_straight_away() {
+    let path = temp_path("negative");
+    fs::write(&path, "{\"time\": -5.0, \"mouth_open\": 0.5}\n").unwrap();
+
+    let mut replay = ReplayTracker::new(path.clone(), 1.0);
+    replay.start().unwrap();
+    let started = Instant::now();
+    assert_eq!(replay.next_report().unwrap().floats["mouth_open"], 0.5);
+    assert!(started.elapsed() < Duration::from_secs(1));
+    replay.shutdown();
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn interrupting_stops_the_wait_for_the_next_report() {
+    let path = temp_path("interrupted");
+    fs::write(
+        &path,
+        "{\"time\": 0.0, \"mouth_open\": 0.25}\n{\"time\": 60.0, \"mouth_open\": 0.5}\n",
+    )
+    .unwrap();
+
+    let mut replay = ReplayTracker::new(path.clone(), 1.0);
+    replay.start().unwrap();
+    assert_eq!(replay.next_report().unwrap().floats["mouth_open"], 0.25);
+
+    let interrupt = replay.interrupter().unwrap();
+    let interrupting = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        interrupt();
+    });
+
+    let started = Instant::now();
+    assert!(replay.next_report().is_none());
+    assert!(started.elapsed() < Duration::from_secs(5));
+    interrupting.join().unwrap();
+    replay.shutdown();
+
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,26 @@
+//! Checks what channels read as when a tracker doesn't report them.
+
+use layertuber::tracker::{FloatSource, Source, TrackingReport, FACE_FOUND};
+
+fn float(report: &TrackingReport, name: &str) -> f32 {
+    FloatSource(name.to_string()).value(report)
+}
+
+#[test]
+fn missing_channels_are_at_rest() {
+    let report = TrackingReport::default();
+
+    // eyes open, mouth shut, and a face in view
+    assert_eq!(float(&report, "blink"), 1.0);
+    assert_eq!(float(&report, "blink_left"), 1.0);
+    assert_eq!(float(&report, "mouth_open"), 0.0);
+    assert_eq!(float(&report, FACE_FOUND), 1.0);
+    assert!(report.face_found());
+}
+
+#[test]
+fn lost_reports_have_no_face() {
+    let report = TrackingReport::lost();
+    assert_eq!(float(&report, FACE_FOUND), 0.0);
+    assert!(!report.face_found());
+}
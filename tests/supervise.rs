@@ -0,0 +1,190 @@
+//! Runs scripted backends under the supervisor, and checks when it restarts them and when it gives
+//! up.
+
+use layertuber::tracker::{
+    Backoff, ControlMessage, RunTrackerError, Tracker, TrackerBackend, TrackerState, TrackingReport,
+};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// what one run of a scripted backend does
+#[derive(Clone, Copy)]
+enum Run {
+    /// fail to start
+    NoStart,
+
+    /// start, make this many reports, then fall over
+    Fail(usize),
+
+    /// start, and keep reporting until told to stop
+    Forever,
+}
+
+/// A backend that does what it's told, and notes when it was started. Once the script runs out,
+/// the last run repeats.
+struct Scripted {
+    script: Vec<Run>,
+    runs: usize,
+    remaining: Option<usize>,
+    stopping: bool,
+    starts: Arc<Mutex<Vec<Instant>>>,
+}
+
+impl TrackerBackend for Scripted {
+    fn start(&mut self) -> Result<(), RunTrackerError> {
+        self.starts.lock().unwrap().push(Instant::now());
+        self.stopping = false;
+
+        let run = self.script[self.runs.min(self.script.len() - 1)];
+        self.runs += 1;
+
+        match run {
+            Run::NoStart => return Err(RunTrackerError::Io(io::Error::other("scripted to fail"))),
+            Run::Fail(reports) => self.remaining = Some(reports),
+            Run::Forever => self.remaining = None,
+        }
+
+        Ok(())
+    }
+
+    fn next_report(&mut self) -> Option<TrackingReport> {
+        if self.stopping {
+            return None;
+        }
+
+        match self.remaining {
+            Some(0) => return None,
+            Some(n) => self.remaining = Some(n - 1),
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+
+        Some(TrackingReport::default())
+    }
+
+    fn send(&mut self, message: ControlMessage) {
+        if let ControlMessage::Die = message {
+            self.stopping = true;
+        }
+    }
+
+    fn failed(&self) -> bool {
+        !self.stopping
+    }
+
+    fn shutdown(&mut self) {}
+}
+
+/// backoff short enough not to keep anyone waiting, but long enough to tell apart from the time it
+/// takes to restart
+fn backoff(initial_millis: u64) -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(initial_millis),
+        max: Duration::from_millis(initial_millis * 4),
+        max_restarts: 5,
+    }
+}
+
+fn spawn(script: &[Run], backoff: Backoff) -> (Tracker, Arc<Mutex<Vec<Instant>>>) {
+    let starts = Arc::new(Mutex::new(Vec::new()));
+    let backend = Scripted {
+        script: script.to_vec(),
+        runs: 0,
+        remaining: None,
+        stopping: false,
+        starts: starts.clone(),
+    };
+
+    (Tracker::spawn(Box::new(backend), backoff), starts)
+}
+
+/// wait for `condition` to hold, for up to `timeout`
+fn eventually(timeout: Duration, condition: impl Fn() -> bool) -> bool {
+    let until = Instant::now() + timeout;
+    while Instant::now() < until {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    condition()
+}
+
+/// how many times the backend has been started
+fn started(starts: &Mutex<Vec<Instant>>) -> usize {
+    starts.lock().unwrap().len()
+}
+
+/// the time between each start and the next
+fn gaps(starts: &Mutex<Vec<Instant>>) -> Vec<Duration> {
+    let starts = starts.lock().unwrap();
+    starts.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+fn reaches(tracker: &Tracker, state: TrackerState, timeout: Duration) -> bool {
+    eventually(timeout, || tracker.state() == state)
+}
+
+#[test]
+fn failed_backends_are_restarted() {
+    let (tracker, starts) = spawn(&[Run::Fail(0), Run::Forever], backoff(100));
+
+    assert!(eventually(Duration::from_secs(5), || started(&starts) == 2));
+    assert!(gaps(&starts)[0] >= Duration::from_millis(100));
+
+    let second = Duration::from_secs(1);
+    assert!(reaches(&tracker, TrackerState::Running, second));
+    assert!(eventually(second, || tracker.reports.latest().is_some()));
+}
+
+#[test]
+fn backoff_starts_over_after_a_report() {
+    let (_tracker, starts) = spawn(
+        &[
+            Run::Fail(0),
+            Run::Fail(0),
+            Run::Fail(1),
+            Run::Fail(0),
+            Run::Forever,
+        ],
+        backoff(100),
+    );
+
+    assert!(eventually(Duration::from_secs(5), || started(&starts) >= 4));
+
+    // the initial wait, then twice that, then back to the initial wait because the third run got
+    // somewhere
+    let gaps = gaps(&starts);
+    let initial = Duration::from_millis(100);
+    assert!(gaps[0] >= initial && gaps[0] < initial * 2, "{:?}", gaps);
+    assert!(gaps[1] >= initial * 2, "{:?}", gaps);
+    assert!(gaps[2] >= initial && gaps[2] < initial * 2, "{:?}", gaps);
+}
+
+#[test]
+fn gives_up_after_too_many_restarts() {
+    let (tracker, starts) = spawn(&[Run::NoStart], backoff(10));
+
+    assert!(reaches(
+        &tracker,
+        TrackerState::Failed,
+        Duration::from_secs(5)
+    ));
+    assert_eq!(started(&starts), 6);
+}
+
+#[test]
+fn stopping_interrupts_the_wait_to_restart() {
+    let (tracker, starts) = spawn(&[Run::Fail(0)], Backoff::default());
+    assert!(reaches(
+        &tracker,
+        TrackerState::Restarting,
+        Duration::from_secs(1)
+    ));
+
+    let dropped = Instant::now();
+    drop(tracker);
+    assert!(dropped.elapsed() < Duration::from_millis(500));
+    assert_eq!(started(&starts), 1);
+}
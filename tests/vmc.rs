@@ -0,0 +1,147 @@
+//! Sends VMC messages to the VMC backend over loopback, standing in for an app like VSeeFace, and
+//! checks what it makes of them.
+
+mod common;
+
+use common::{close, free_port};
+use layertuber::tracker::{Calibration, TrackerBackend, VmcTracker};
+use std::net::{Ipv4Addr, UdpSocket};
+
+enum Arg<'a> {
+    S(&'a str),
+    F(f32),
+}
+
+fn osc_string(bytes: &mut Vec<u8>, s: &str) {
+    bytes.extend_from_slice(s.as_bytes());
+    bytes.resize(bytes.len() + 4 - s.len() % 4, 0);
+}
+
+fn message(address: &str, args: &[Arg]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    osc_string(&mut bytes, address);
+
+    let tags: String = args
+        .iter()
+        .map(|a| match a {
+            Arg::S(_) => 's',
+            Arg::F(_) => 'f',
+        })
+        .collect();
+    osc_string(&mut bytes, &format!(",{}", tags));
+
+    for arg in args {
+        match arg {
+            Arg::S(s) => osc_string(&mut bytes, s),
+            Arg::F(f) => bytes.extend_from_slice(&f.to_be_bytes()),
+        }
+    }
+
+    bytes
+}
+
+fn bundle(messages: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = b"#bundle\0".to_vec();
+    bytes.extend_from_slice(&1u64.to_be_bytes()); // "immediately"
+
+    for m in messages {
+        bytes.extend_from_slice(&(m.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(m);
+    }
+
+    bytes
+}
+
+fn head(rotation: [f32; 4]) -> Vec<u8> {
+    let [x, y, z, w] = rotation;
+    message(
+        "/VMC/Ext/Bone/Pos",
+        &[
+            Arg::S("Head"),
+            Arg::F(0.0),
+            Arg::F(0.1),
+            Arg::F(0.0),
+            Arg::F(x),
+            Arg::F(y),
+            Arg::F(z),
+            Arg::F(w),
+        ],
+    )
+}
+
+fn blend(name: &str, value: f32) -> Vec<u8> {
+    message("/VMC/Ext/Blend/Val", &[Arg::S(name), Arg::F(value)])
+}
+
+fn apply() -> Vec<u8> {
+    message("/VMC/Ext/Blend/Apply", &[])
+}
+
+#[test]
+fn maps_blendshapes_and_head_to_channels() {
+    let port = free_port();
+    let mut tracker = VmcTracker::new(port, Calibration::new());
+    tracker.start().unwrap();
+
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let send = |packet: Vec<u8>| {
+        sender
+            .send_to(&packet, (Ipv4Addr::LOCALHOST, port))
+            .unwrap();
+    };
+
+    send(bundle(&[
+        head([0.0, 0.0, 0.0, 1.0]),
+        blend("A", 0.0),
+        apply(),
+    ]));
+    let neutral = tracker.next_report().unwrap();
+    assert_eq!(neutral.floats["blink"], 1.0);
+    assert_eq!(neutral.rotations["head_rotation"], [0.0, 0.0, 0.0, 1.0]);
+
+    // a sender with only one blink closes both eyes with it
+    send(bundle(&[blend("Blink", 0.8), apply()]));
+    let report = tracker.next_report().unwrap();
+    assert!(close(report.floats["blink"], 0.2));
+    assert!(close(report.floats["blink_left"], 0.2));
+    assert!(close(report.floats["blink_right"], 0.2));
+
+    // about 20 degrees of yaw, and values spread over several packets before they're applied
+    send(head([0.0, 0.1736, 0.0, 0.9848]));
+    send(bundle(&[
+        blend("A", 0.7),
+        blend("Blink", 0.0),
+        blend("Blink_L", 1.0),
+        blend("jawForward", 0.25),
+    ]));
+    send(apply());
+
+    let report = tracker.next_report().unwrap();
+    assert_eq!(report.floats["mouth_open"], 0.7);
+    assert_eq!(report.floats["A"], 0.7);
+    assert_eq!(report.floats["jawForward"], 0.25);
+    assert_eq!(report.floats["blink_left"], 0.0);
+    assert_eq!(report.floats["blink_right"], 1.0);
+    assert_eq!(report.floats["blink"], 1.0);
+    assert!(close(report.rotations["head_rotation"][1], 0.1736));
+
+    // garbage is skipped
+    send(b"not osc".to_vec());
+    send(message("/VMC/Ext/OK", &[Arg::F(1.0)]));
+    send(bundle(&[blend("A", 0.1), apply()]));
+    assert_eq!(tracker.next_report().unwrap().floats["A"], 0.1);
+
+    // newer senders say when they've lost the face, and carry on sending old values
+    let ok = |tracking: f32| {
+        message(
+            "/VMC/Ext/OK",
+            &[Arg::F(1.0), Arg::F(3.0), Arg::F(0.0), Arg::F(tracking)],
+        )
+    };
+    send(bundle(&[ok(0.0), blend("A", 0.1), apply()]));
+    assert!(!tracker.next_report().unwrap().face_found());
+    send(bundle(&[ok(1.0), blend("A", 0.1), apply()]));
+    assert!(tracker.next_report().unwrap().face_found());
+
+    tracker.shutdown();
+}